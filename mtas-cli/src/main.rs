@@ -1,3 +1,184 @@
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    Ok(())
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use mtas_controller::{
+    BenchConfig, BenchReport, Command, Platform, RoundTripConfig, ThroughputConfig, controller,
+    run_benchmark,
+};
+
+const USAGE: &str = "\
+Usage: mtas-cli bench <backend uri> [options]
+
+The uri picks the backend, e.g. adb://emulator-5554 or adb://emulator-5554?touch=minitouch.
+
+Options:
+  --frames <n>      frames sampled for capture latency and interval (default 100)
+  --tap <x>,<y>     measure touch round trip at this point, also used for throughput
+  --samples <n>     round trip samples (default 10)
+  --commands <n>    taps sent to measure throughput, needs --tap (default 0)
+  --timeout <ms>    longest wait for a frame (default 2000)";
+
+fn main() -> Result<()> {
+    // Logs go to stderr, stdout is left to the report
+    mtas_logger::init_logger!(std::io::stderr());
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("bench") => {
+            print!("{}", bench(&args[1..])?);
+            Ok(())
+        }
+        _ => Err(anyhow!("{USAGE}")),
+    }
+}
+
+fn bench(args: &[String]) -> Result<BenchReport> {
+    let (uri, options) = args.split_first().ok_or(anyhow!("{USAGE}"))?;
+
+    let mut config = BenchConfig::default();
+    let mut tap = None;
+    let mut samples = 10;
+    let mut commands = 0;
+
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .ok_or(anyhow!("{option} needs a value\n\n{USAGE}"))?;
+        match option.as_str() {
+            "--frames" => config.frames = value.parse()?,
+            "--tap" => {
+                let (x, y) = value
+                    .split_once(',')
+                    .ok_or(anyhow!("--tap takes <x>,<y>"))?;
+                tap = Some((x.trim().parse()?, y.trim().parse()?));
+            }
+            "--samples" => samples = value.parse()?,
+            "--commands" => commands = value.parse()?,
+            "--timeout" => config.timeout = Duration::from_millis(value.parse()?),
+            _ => return Err(anyhow!("Unknown option {option}\n\n{USAGE}")),
+        }
+    }
+
+    if commands > 0 && tap.is_none() {
+        return Err(anyhow!("--commands needs --tap\n\n{USAGE}"));
+    }
+
+    if let Some((x, y)) = tap {
+        config.round_trip = Some(RoundTripConfig {
+            x,
+            y,
+            samples,
+            region: None,
+        });
+        if commands > 0 {
+            config.throughput = Some(ThroughputConfig {
+                command: Command::Tab { x, y },
+                count: commands,
+            });
+        }
+    }
+
+    let (mut controller, screen_cap) = controller(Platform::Uri(uri.clone()))?;
+    Ok(run_benchmark(&mut controller, &screen_cap, &config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+        thread::spawn,
+        time::Instant,
+    };
+
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    use super::*;
+
+    /// How long the fake device shows a tap.
+    const SHOW_TAP: Duration = Duration::from_millis(300);
+
+    fn read_request(stream: &mut TcpStream) -> Option<String> {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).ok()?;
+        let len = usize::from_str_radix(std::str::from_utf8(&len).ok()?, 16).ok()?;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).ok()?;
+        String::from_utf8(payload).ok()
+    }
+
+    /// Adb server of a device without minitouch that shows taps for [`SHOW_TAP`], returns its
+    /// address and every `input` it ran.
+    fn fake_adb() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let inputs = Arc::new(Mutex::new(Vec::new()));
+        let recorded = inputs.clone();
+
+        spawn(move || {
+            let mut tapped: Option<Instant> = None;
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+
+                while let Some(request) = read_request(&mut stream) {
+                    let response = if request.starts_with("host:transport") {
+                        stream.write_all(b"OKAY").unwrap();
+                        continue;
+                    } else if request == "exec:screencap -p" {
+                        let mut screen = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 255]));
+                        if tapped.is_some_and(|at| at.elapsed() < SHOW_TAP) {
+                            screen.put_pixel(4, 4, Rgba([255, 255, 255, 255]));
+                        }
+                        let mut png = Cursor::new(Vec::new());
+                        screen.write_to(&mut png, ImageFormat::Png).unwrap();
+                        png.into_inner()
+                    } else if let Some(input) = request.strip_prefix("shell:input ") {
+                        recorded.lock().unwrap().push(input.to_string());
+                        tapped = Some(Instant::now());
+                        Vec::new()
+                    } else {
+                        let msg = format!("unknown service {request}");
+                        format!("FAIL{:04x}{msg}", msg.len()).into_bytes()
+                    };
+
+                    if !response.starts_with(b"FAIL") {
+                        stream.write_all(b"OKAY").unwrap();
+                    }
+                    stream.write_all(&response).unwrap();
+                    break;
+                }
+            }
+        });
+
+        (addr, inputs)
+    }
+
+    #[test]
+    fn test_bench_adb_input() -> Result<()> {
+        let (addr, inputs) = fake_adb();
+
+        let args = [
+            &format!("adb://?server={addr}"),
+            "--frames",
+            "3",
+            "--tap",
+            "4,4",
+            "--samples",
+            "2",
+            "--commands",
+            "2",
+        ]
+        .map(String::from);
+        let report = bench(&args)?;
+
+        let round_trip = report.round_trip.ok_or(anyhow!("no round trip"))?;
+        assert_eq!(round_trip.count, 2);
+        assert!(report.throughput.is_some());
+        assert_eq!(*inputs.lock().unwrap(), ["tap 4 4"; 4]);
+
+        Ok(())
+    }
 }
@@ -4,6 +4,11 @@ use std::path::PathBuf;
 fn main() {
     println!("cargo:rerun-if-changed=src/mumu/external_renderer_ipc.h");
 
+    // The MuMu bindings are only compiled on windows, skip bindgen (and libclang) elsewhere
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") {
+        return;
+    }
+
     let bindings = bindgen::Builder::default()
         .header("src/mumu/external_renderer_ipc.h")
         .ctypes_prefix("::std::os::raw")
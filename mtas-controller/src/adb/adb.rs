@@ -0,0 +1,777 @@
+use std::{collections::BTreeSet, net::TcpStream, sync::mpsc::SendError, time::Duration};
+
+use crate::{
+    CaptureHandle, CaptureTimeout, Command, ControllerTrait, ErrorClass, Frame, FrameSource,
+    InputSpace, KeyAction, KeyCode, Return, ScreenCapCommand, ScreenCapture,
+    adb::AdbClient,
+    minitouch::{MinitouchError, MinitouchTransport},
+    spawn_capture,
+    touch::{
+        Gesture, GestureError, HoldError, HoldTouch, SwipeProfile, TouchSink, long_press, run_path,
+        run_timeline,
+    },
+};
+use image::RgbaImage;
+use mtas_utils::CoordError;
+use thiserror::Error;
+
+use tracing::*;
+
+/// How frames are pulled from the device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AdbCapture {
+    /// `screencap -p`, PNG encoded, slower but works everywhere.
+    #[default]
+    Png,
+    /// `screencap` raw framebuffer dump, RGBA_8888 only.
+    Raw,
+}
+
+/// How touches are injected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AdbTouch {
+    /// `input tap`/`input swipe` shell commands.
+    #[default]
+    Input,
+    /// A minitouch compatible server listening on the abstract unix socket `socket`.
+    Minitouch { socket: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct AdbConfig {
+    /// Address of the adb server, `127.0.0.1:5037` by default.
+    pub server: String,
+    pub serial: Option<String>,
+    pub capture: AdbCapture,
+    pub touch: AdbTouch,
+}
+
+impl Default for AdbConfig {
+    fn default() -> Self {
+        AdbConfig {
+            server: "127.0.0.1:5037".to_string(),
+            serial: None,
+            capture: AdbCapture::default(),
+            touch: AdbTouch::default(),
+        }
+    }
+}
+
+pub struct AdbController {
+    capture: CaptureHandle,
+    client: AdbClient,
+    touch: Option<MinitouchTransport<TcpStream>>,
+    swipe: SwipeProfile,
+    held: BTreeSet<u32>,
+    input: InputSpace,
+}
+
+#[derive(Error, Debug)]
+pub enum AdbError {
+    #[error("Adb Connection Failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The adb server or device refused a request.
+    #[error("Adb Request Failed: {0}")]
+    Fail(String),
+
+    /// `input` printed a usage or permission error, it may have done part of the work.
+    #[error("Adb Input Rejected: {0}")]
+    Input(String),
+
+    #[error("Adb Protocol Error: {0}")]
+    Protocol(String),
+
+    #[error("Adb Screencap Decode Failed: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Screen Capture Command Failed to Send：{0}")]
+    ScreenCap(#[from] SendError<ScreenCapCommand>),
+
+    #[error("Screen Capture Timing Failed: {0}")]
+    CaptureTimeout(#[from] CaptureTimeout),
+
+    #[error("Not Supported Over Adb: {0}")]
+    Unsupported(String),
+
+    #[error("Invalid Gesture: {0}")]
+    Gesture(#[from] GestureError),
+
+    #[error("Adb Minitouch Error: {0}")]
+    Minitouch(#[from] MinitouchError),
+
+    #[error("Invalid Touch: {0}")]
+    Hold(#[from] HoldError),
+
+    #[error("Invalid Point: {0}")]
+    Coord(#[from] CoordError),
+}
+
+impl AdbError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            AdbError::Io(_)
+            | AdbError::Protocol(_)
+            | AdbError::ScreenCap(_)
+            | AdbError::CaptureTimeout(_) => ErrorClass::NeedsReconnect,
+            AdbError::Fail(_) if self.device_not_ready() => ErrorClass::Transient,
+            AdbError::Image(_) => ErrorClass::Transient,
+            // Unknown serial or service, or an `input` that may have been half applied
+            AdbError::Fail(_) | AdbError::Input(_) => ErrorClass::Fatal,
+            AdbError::Minitouch(MinitouchError::Io(_) | MinitouchError::Handshake(_)) => {
+                ErrorClass::NeedsReconnect
+            }
+            AdbError::Minitouch(MinitouchError::ContactOutOfRange { .. })
+            | AdbError::Unsupported(_)
+            | AdbError::Gesture(_)
+            | AdbError::Hold(_)
+            | AdbError::Coord(_) => ErrorClass::InvalidArgument,
+        }
+    }
+
+    /// Whether the command never reached the device, see
+    /// [`crate::ControllerError::is_unsent`]. A dropped adb connection may have lost the reply
+    /// of an `input` that already ran.
+    pub fn is_unsent(&self) -> bool {
+        matches!(self, AdbError::ScreenCap(_))
+    }
+
+    /// A device that is offline or still connecting, the request never reached it.
+    fn device_not_ready(&self) -> bool {
+        let AdbError::Fail(message) = self else {
+            return false;
+        };
+        message.contains("offline") || message.contains("still")
+    }
+}
+
+struct AdbFrameSource {
+    client: AdbClient,
+    method: AdbCapture,
+}
+
+impl FrameSource for AdbFrameSource {
+    type Error = AdbError;
+
+    fn capture(&mut self, frame: &mut Frame) -> Result<(), AdbError> {
+        let img = screencap(&self.client, self.method)?;
+
+        frame.resize(img.width() as usize, img.height() as usize);
+        frame.data.copy_from_slice(img.as_raw());
+
+        Ok(())
+    }
+}
+
+pub fn screencap(client: &AdbClient, method: AdbCapture) -> Result<RgbaImage, AdbError> {
+    match method {
+        AdbCapture::Png => {
+            let png = client.exec("screencap -p")?;
+            Ok(image::load_from_memory_with_format(&png, image::ImageFormat::Png)?.to_rgba8())
+        }
+        AdbCapture::Raw => parse_raw_screencap(client.exec("screencap")?),
+    }
+}
+
+/// Parse the `screencap` dump: `width`, `height`, `format` (and since Android 9 a
+/// `colorspace`) as little endian u32, followed by the pixels.
+fn parse_raw_screencap(mut raw: Vec<u8>) -> Result<RgbaImage, AdbError> {
+    const RGBA_8888: u32 = 1;
+
+    let field = |raw: &[u8], i: usize| -> Result<u32, AdbError> {
+        raw.get(i * 4..i * 4 + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| AdbError::Protocol("screencap header truncated".to_string()))
+    };
+
+    let width = field(&raw, 0)?;
+    let height = field(&raw, 1)?;
+    let format = field(&raw, 2)?;
+
+    if format != RGBA_8888 {
+        return Err(AdbError::Protocol(format!(
+            "unsupported screencap pixel format {format}"
+        )));
+    }
+
+    let pixels = width as usize * height as usize * 4;
+    let header = match raw.len().checked_sub(pixels) {
+        Some(header @ (12 | 16)) => header,
+        _ => {
+            return Err(AdbError::Protocol(format!(
+                "screencap size {} does not match {width}x{height}",
+                raw.len()
+            )));
+        }
+    };
+
+    raw.drain(..header);
+    RgbaImage::from_raw(width, height, raw)
+        .ok_or_else(|| AdbError::Protocol("screencap buffer too small".to_string()))
+}
+
+/// `input text` reads `%s` as a space and has no escape for it, so the text is split after
+/// every `%` followed by an `s` and each part typed on its own. Parts are single quoted for
+/// the device shell.
+fn input_text_args(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(i) = rest.find("%s") {
+        parts.push(&rest[..=i]);
+        rest = &rest[i + 1..];
+    }
+    parts.push(rest);
+
+    parts
+        .into_iter()
+        .map(|part| format!("'{}'", part.replace(' ', "%s").replace('\'', "'\\''")))
+        .collect()
+}
+
+/// Needs minitouch, `input` has no separate press and release.
+impl TouchSink for AdbController {
+    type Error = AdbError;
+
+    fn touch_down(&mut self, contact: u32, x: i32, y: i32) -> Result<(), AdbError> {
+        Ok(self.minitouch()?.touch_down(contact, x, y)?)
+    }
+
+    fn touch_move(&mut self, contact: u32, x: i32, y: i32) -> Result<(), AdbError> {
+        Ok(self.minitouch()?.touch_move(contact, x, y)?)
+    }
+
+    fn touch_up(&mut self, contact: u32) -> Result<(), AdbError> {
+        Ok(self.minitouch()?.touch_up(contact)?)
+    }
+}
+
+impl HoldTouch for AdbController {
+    fn held(&mut self) -> &mut BTreeSet<u32> {
+        &mut self.held
+    }
+}
+
+impl Drop for AdbController {
+    fn drop(&mut self) {
+        if let Err(e) = self.release_held() {
+            warn!("Failed to release held touches: {}", e);
+        }
+    }
+}
+
+impl ControllerTrait for AdbController {
+    type Config = AdbConfig;
+    type Error = AdbError;
+
+    fn new(config: AdbConfig) -> Result<(Self, ScreenCapture), AdbError> {
+        let client = AdbClient::new(config.server, config.serial);
+
+        let (width, height) = screencap(&client, config.capture)?.dimensions();
+
+        info!("Adb device {:?} is {}x{}", client.serial, width, height);
+
+        let touch = match &config.touch {
+            AdbTouch::Input => None,
+            AdbTouch::Minitouch { socket } => {
+                let stream = client.open(&format!("localabstract:{socket}"))?;
+                let mut touch = MinitouchTransport::new(stream)?;
+                touch.set_screen_size(width, height);
+                Some(touch)
+            }
+        };
+
+        let (capture, screen_capture) = spawn_capture(
+            AdbFrameSource {
+                client: client.clone(),
+                method: config.capture,
+            },
+            width as usize,
+            height as usize,
+        );
+
+        Ok((
+            AdbController {
+                capture,
+                client,
+                touch,
+                swipe: SwipeProfile::default(),
+                held: BTreeSet::new(),
+                input: InputSpace::default(),
+            },
+            screen_capture,
+        ))
+    }
+
+    #[instrument(skip_all)]
+    fn execute(&mut self, command: Command) -> Result<Return, AdbError> {
+        let result = self.dispatch(command);
+
+        if result.is_err()
+            && let Err(e) = self.release_held()
+        {
+            warn!("Failed to release held touches: {}", e);
+        }
+        result
+    }
+}
+
+impl AdbController {
+    fn dispatch(&mut self, command: Command) -> Result<Return, AdbError> {
+        let space = self.input.coord_space(self.capture.frame_size());
+        command.check(&space)?;
+
+        match command {
+            Command::Tab { x, y } => self.tab(x, y),
+            Command::LongPress { x, y, hold } => self.long_press(x, y, hold),
+            Command::TouchDown { contact, x, y } => {
+                self.hold_down(contact, x, y)?;
+                Ok(Return::Nothing)
+            }
+            Command::TouchMove { contact, x, y } => {
+                self.hold_move(contact, x, y)?;
+                Ok(Return::Nothing)
+            }
+            Command::TouchUp { contact } => {
+                self.hold_up(contact)?;
+                Ok(Return::Nothing)
+            }
+            Command::Scroll { x1, y1, x2, y2, t } => self.scroll(x1, y1, x2, y2, t),
+            Command::ControlScreenCapture { start } => {
+                Ok(self.capture.control_screen_capture(start)?)
+            }
+            Command::TestScreenShotDelay {} => self.capture.test_screen_shot_delay(),
+            Command::SetFrameRate(rate) => Ok(self.capture.set_frame_rate(rate)?),
+            Command::Key { code, action } => self.key(code, action),
+            Command::Text(text) => self.text(&text),
+            Command::Gesture(gesture) => self.gesture(&gesture),
+            Command::SetSwipeProfile(profile) => {
+                self.swipe = profile;
+                Ok(Return::Nothing)
+            }
+            Command::SetInputSpace(input) => {
+                self.input = input;
+                Ok(Return::Nothing)
+            }
+            command @ (Command::TabAt(_)
+            | Command::LongPressAt { .. }
+            | Command::ScrollAt { .. }) => self.dispatch(command.resolve(&space)?),
+            Command::BindDisplay(target) => {
+                Err(AdbError::Unsupported(format!("binding display {target:?}")))
+            }
+        }
+    }
+}
+
+impl AdbController {
+    pub fn client(&self) -> &AdbClient {
+        &self.client
+    }
+
+    fn minitouch(&mut self) -> Result<&mut MinitouchTransport<TcpStream>, AdbError> {
+        self.touch.as_mut().ok_or_else(|| {
+            AdbError::Unsupported("separate touch down and up without minitouch".to_string())
+        })
+    }
+
+    pub fn tab(&mut self, x: i32, y: i32) -> Result<Return, AdbError> {
+        let contact = self.free_contact();
+        match &mut self.touch {
+            Some(touch) => {
+                touch.down(contact, x, y)?.commit()?;
+                touch.up(contact)?.commit()?;
+                Ok(Return::Nothing)
+            }
+            None => self.input(&format!("tap {x} {y}")),
+        }
+    }
+
+    /// Without minitouch this is a swipe that does not move.
+    pub fn long_press(&mut self, x: i32, y: i32, hold: Duration) -> Result<Return, AdbError> {
+        let contact = self.free_contact();
+        match &mut self.touch {
+            Some(touch) => long_press(touch, contact, x, y, hold)?,
+            None => return self.input(&format!("swipe {x} {y} {x} {y} {}", hold.as_millis())),
+        }
+
+        Ok(Return::Nothing)
+    }
+
+    pub fn scroll(
+        &mut self,
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        t: Duration,
+    ) -> Result<Return, AdbError> {
+        let contact = self.free_contact();
+        // `input swipe` only knows a linear move, the profile needs minitouch
+        let Some(touch) = &mut self.touch else {
+            return self.input(&format!("swipe {x1} {y1} {x2} {y2} {}", t.as_millis()));
+        };
+
+        run_path(touch, contact, &self.swipe.path(x1, y1, x2, y2, t))?;
+
+        Ok(Return::Nothing)
+    }
+
+    /// Needs minitouch, `input` can only drive one finger.
+    pub fn gesture(&mut self, gesture: &Gesture) -> Result<Return, AdbError> {
+        self.check_gesture(gesture)?;
+        let timeline = gesture.timeline(self.swipe.rate)?;
+
+        let Some(touch) = &mut self.touch else {
+            return Err(AdbError::Unsupported(
+                "multi-finger gestures without minitouch".to_string(),
+            ));
+        };
+
+        run_timeline(touch, &timeline)?;
+
+        Ok(Return::Nothing)
+    }
+
+    pub fn keyevent(&self, code: i32) -> Result<Return, AdbError> {
+        self.input(&format!("keyevent {code}"))
+    }
+
+    /// `input` can only press and release, holding a key is not possible over adb.
+    pub fn key(&self, code: KeyCode, action: KeyAction) -> Result<Return, AdbError> {
+        match action {
+            KeyAction::Press => self.keyevent(code.android()),
+            KeyAction::Down | KeyAction::Up => {
+                Err(AdbError::Unsupported(format!("key {action:?} of {code:?}")))
+            }
+        }
+    }
+
+    /// `input text` only types ASCII.
+    pub fn text(&self, text: &str) -> Result<Return, AdbError> {
+        if !text.is_ascii() {
+            return Err(AdbError::Unsupported(format!(
+                "non ASCII text {text:?} without an IME"
+            )));
+        }
+
+        for arg in input_text_args(text) {
+            self.input(&format!("text {arg}"))?;
+        }
+
+        Ok(Return::Nothing)
+    }
+
+    fn input(&self, args: &str) -> Result<Return, AdbError> {
+        let output = self.client.shell(&format!("input {args}"))?;
+
+        // `input` prints nothing on success, anything else is a usage or permission error
+        if !output.is_empty() {
+            return Err(AdbError::Input(
+                String::from_utf8_lossy(&output).trim().to_string(),
+            ));
+        }
+
+        Ok(Return::Nothing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+        thread::spawn,
+        time::Duration,
+    };
+
+    use anyhow::Result;
+    use image::Rgba;
+
+    use super::*;
+
+    /// Fake adb server replaying canned responses per service and recording requests.
+    pub(crate) struct FakeAdb {
+        pub addr: String,
+        pub requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    fn read_request(stream: &mut TcpStream) -> Option<String> {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).ok()?;
+        let len = usize::from_str_radix(std::str::from_utf8(&len).ok()?, 16).ok()?;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).ok()?;
+        String::from_utf8(payload).ok()
+    }
+
+    impl FakeAdb {
+        pub fn start(responses: HashMap<String, Vec<u8>>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+
+            spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else { break };
+
+                    while let Some(request) = read_request(&mut stream) {
+                        recorded.lock().unwrap().push(request.clone());
+
+                        if request.starts_with("host:transport") {
+                            stream.write_all(b"OKAY").unwrap();
+                            continue;
+                        }
+
+                        match responses.get(&request) {
+                            Some(body) => {
+                                stream.write_all(b"OKAY").unwrap();
+                                stream.write_all(body).unwrap();
+
+                                if request.starts_with("localabstract:") {
+                                    let mut written = String::new();
+                                    let _ = stream.read_to_string(&mut written);
+                                    recorded.lock().unwrap().push(written);
+                                }
+                            }
+                            None => {
+                                let msg = format!("unknown service {request}");
+                                stream
+                                    .write_all(format!("FAIL{:04x}{msg}", msg.len()).as_bytes())
+                                    .unwrap();
+                            }
+                        }
+                        break;
+                    }
+                }
+            });
+
+            FakeAdb { addr, requests }
+        }
+
+        pub fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let img = RgbaImage::from_pixel(width, height, Rgba([1, 2, 3, 255]));
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    fn config(fake: &FakeAdb, capture: AdbCapture) -> AdbConfig {
+        AdbConfig {
+            server: fake.addr.clone(),
+            serial: Some("emulator-5554".to_string()),
+            capture,
+            touch: AdbTouch::Input,
+        }
+    }
+
+    #[test]
+    fn test_adb_tap_and_swipe() -> Result<()> {
+        let fake = FakeAdb::start(HashMap::from([
+            ("exec:screencap -p".to_string(), png(32, 32)),
+            ("shell:input tap 10 20".to_string(), vec![]),
+            ("shell:input swipe 1 2 3 4 150".to_string(), vec![]),
+            ("shell:input swipe 5 6 5 6 800".to_string(), vec![]),
+        ]));
+
+        let (mut controller, screen_cap) = AdbController::new(config(&fake, AdbCapture::Png))?;
+        assert_eq!((screen_cap.width, screen_cap.height), (32, 32));
+
+        controller.execute(Command::Tab { x: 10, y: 20 })?;
+        controller.execute(Command::Scroll {
+            x1: 1,
+            y1: 2,
+            x2: 3,
+            y2: 4,
+            t: Duration::from_millis(150),
+        })?;
+        controller.execute(Command::LongPress {
+            x: 5,
+            y: 6,
+            hold: Duration::from_millis(800),
+        })?;
+
+        assert!(matches!(
+            controller.execute(Command::TouchDown {
+                contact: 0,
+                x: 0,
+                y: 0
+            }),
+            Err(AdbError::Unsupported(_))
+        ));
+
+        assert_eq!(
+            fake.requests(),
+            vec![
+                "host:transport:emulator-5554",
+                "exec:screencap -p",
+                "host:transport:emulator-5554",
+                "shell:input tap 10 20",
+                "host:transport:emulator-5554",
+                "shell:input swipe 1 2 3 4 150",
+                "host:transport:emulator-5554",
+                "shell:input swipe 5 6 5 6 800",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_adb_raw_screencap() -> Result<()> {
+        let mut raw = Vec::new();
+        for field in [2u32, 1, 1, 0] {
+            raw.extend_from_slice(&field.to_le_bytes());
+        }
+        raw.extend_from_slice(&[9, 8, 7, 255, 6, 5, 4, 255]);
+
+        let fake = FakeAdb::start(HashMap::from([("exec:screencap".to_string(), raw)]));
+        let client = AdbClient::new(fake.addr.clone(), None);
+
+        let img = screencap(&client, AdbCapture::Raw)?;
+        assert_eq!(img.dimensions(), (2, 1));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([6, 5, 4, 255]));
+        assert_eq!(fake.requests()[0], "host:transport-any");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_adb_fail_response() {
+        let fake = FakeAdb::start(HashMap::new());
+        let client = AdbClient::new(fake.addr.clone(), None);
+
+        match client.shell("input tap 1 1") {
+            Err(e @ AdbError::Fail(_)) => {
+                assert!(e.to_string().contains("unknown service"));
+                assert_eq!(e.class(), ErrorClass::Fatal);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let offline = AdbError::Fail("device offline".to_string());
+        assert_eq!(offline.class(), ErrorClass::Transient);
+    }
+
+    #[test]
+    fn test_adb_input_error_output() -> Result<()> {
+        let fake = FakeAdb::start(HashMap::from([
+            ("exec:screencap -p".to_string(), png(1, 1)),
+            (
+                "shell:input keyevent 4".to_string(),
+                b"Error: permission denied\n".to_vec(),
+            ),
+        ]));
+
+        let (controller, _screen_cap) = AdbController::new(config(&fake, AdbCapture::Png))?;
+
+        let error = controller.keyevent(4).err();
+        assert!(matches!(error, Some(AdbError::Input(_))));
+        assert!(error.is_some_and(|e| !e.class().is_retryable()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_adb_key_and_text() -> Result<()> {
+        let fake = FakeAdb::start(HashMap::from([
+            ("exec:screencap -p".to_string(), png(1, 1)),
+            ("shell:input keyevent 4".to_string(), vec![]),
+            ("shell:input text 'it'\\''s%sme;'".to_string(), vec![]),
+            ("shell:input text '100%'".to_string(), vec![]),
+            ("shell:input text 'sure%s50%%soff'".to_string(), vec![]),
+        ]));
+
+        let (mut controller, _screen_cap) = AdbController::new(config(&fake, AdbCapture::Png))?;
+
+        controller.execute(Command::Key {
+            code: KeyCode::Back,
+            action: KeyAction::Press,
+        })?;
+        controller.execute(Command::Text("it's me;".to_string()))?;
+
+        // A literal `%s` is typed in two parts so it does not turn into a space
+        controller.execute(Command::Text("100%sure 50% off".to_string()))?;
+        let texts: Vec<_> = fake
+            .requests()
+            .into_iter()
+            .filter(|r| r.starts_with("shell:input text"))
+            .collect();
+        assert_eq!(
+            texts[1..],
+            [
+                "shell:input text '100%'",
+                "shell:input text 'sure%s50%%soff'"
+            ]
+        );
+
+        assert!(matches!(
+            controller.key(KeyCode::Back, KeyAction::Down),
+            Err(AdbError::Unsupported(_))
+        ));
+        assert!(matches!(
+            controller.text("你好"),
+            Err(AdbError::Unsupported(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_adb_minitouch_touch() -> Result<()> {
+        let fake = FakeAdb::start(HashMap::from([
+            ("exec:screencap -p".to_string(), png(100, 200)),
+            (
+                "localabstract:minitouch".to_string(),
+                b"v 1\n^ 10 199 399 255\n$ 7\n".to_vec(),
+            ),
+        ]));
+
+        let mut config = config(&fake, AdbCapture::Png);
+        config.touch = AdbTouch::Minitouch {
+            socket: "minitouch".to_string(),
+        };
+
+        let (mut controller, _screen_cap) = AdbController::new(config)?;
+        controller.execute(Command::Tab { x: 50, y: 100 })?;
+        controller.execute(Command::Scroll {
+            x1: 0,
+            y1: 0,
+            x2: 10,
+            y2: 20,
+            t: Duration::from_millis(32),
+        })?;
+        controller.execute(Command::TouchDown {
+            contact: 1,
+            x: 10,
+            y: 10,
+        })?;
+        controller.execute(Command::LongPress {
+            x: 20,
+            y: 20,
+            hold: Duration::from_millis(5),
+        })?;
+        drop(controller);
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while fake.requests().len() < 5 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(
+            fake.requests()[4],
+            "d 0 99 199 50\nc\nu 0\nc\n\
+             d 0 0 0 50\nc\nm 0 9 19 50\nc\nm 0 19 39 50\nc\nu 0\nc\n\
+             d 1 19 19 50\nc\nd 0 39 39 50\nc\nu 0\nc\nu 1\nc\n"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use crate::adb::AdbError;
+
+/// Minimal client of the adb host protocol.
+///
+/// Every service runs on its own connection to the adb server: the request is sent as
+/// `<4 hex digit length><payload>` and answered with `OKAY` or `FAIL<len><message>`.
+#[derive(Clone, Debug)]
+pub struct AdbClient {
+    pub server: String,
+    /// Device serial, `None` means the only connected device.
+    pub serial: Option<String>,
+    pub timeout: Duration,
+}
+
+impl AdbClient {
+    pub fn new(server: impl Into<String>, serial: Option<String>) -> Self {
+        AdbClient {
+            server: server.into(),
+            serial,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Open a connection already switched to the device transport.
+    pub fn transport(&self) -> Result<TcpStream, AdbError> {
+        let mut stream = TcpStream::connect(&self.server)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let request = match &self.serial {
+            Some(serial) => format!("host:transport:{serial}"),
+            None => "host:transport-any".to_string(),
+        };
+        send_request(&mut stream, &request)?;
+
+        Ok(stream)
+    }
+
+    /// Open a bidirectional stream to `service`, e.g. `localabstract:minitouch`.
+    pub fn open(&self, service: &str) -> Result<TcpStream, AdbError> {
+        let mut stream = self.transport()?;
+        send_request(&mut stream, service)?;
+
+        Ok(stream)
+    }
+
+    /// Run `service` on the device and return everything it writes until EOF.
+    pub fn service(&self, service: &str) -> Result<Vec<u8>, AdbError> {
+        let mut stream = self.open(service)?;
+
+        let mut output = Vec::new();
+        stream.read_to_end(&mut output)?;
+
+        Ok(output)
+    }
+
+    /// `shell:` service, the output may have `\n` rewritten to `\r\n` on old devices.
+    pub fn shell(&self, command: &str) -> Result<Vec<u8>, AdbError> {
+        self.service(&format!("shell:{command}"))
+    }
+
+    /// `exec:` service, binary safe output.
+    pub fn exec(&self, command: &str) -> Result<Vec<u8>, AdbError> {
+        self.service(&format!("exec:{command}"))
+    }
+}
+
+pub(crate) fn send_request(stream: &mut TcpStream, payload: &str) -> Result<(), AdbError> {
+    stream.write_all(format!("{:04x}{}", payload.len(), payload).as_bytes())?;
+    read_status(stream)
+}
+
+fn read_status(stream: &mut TcpStream) -> Result<(), AdbError> {
+    let mut status = [0u8; 4];
+    stream.read_exact(&mut status)?;
+
+    match &status {
+        b"OKAY" => Ok(()),
+        b"FAIL" => {
+            let mut len = [0u8; 4];
+            stream.read_exact(&mut len)?;
+            let len = parse_hex_len(&len)?;
+
+            let mut message = vec![0u8; len];
+            stream.read_exact(&mut message)?;
+
+            Err(AdbError::Fail(
+                String::from_utf8_lossy(&message).into_owned(),
+            ))
+        }
+        other => Err(AdbError::Protocol(format!(
+            "unexpected status {:?}",
+            String::from_utf8_lossy(other)
+        ))),
+    }
+}
+
+fn parse_hex_len(len: &[u8; 4]) -> Result<usize, AdbError> {
+    std::str::from_utf8(len)
+        .ok()
+        .and_then(|s| usize::from_str_radix(s, 16).ok())
+        .ok_or_else(|| AdbError::Protocol(format!("invalid length {:?}", len)))
+}
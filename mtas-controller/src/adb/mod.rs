@@ -0,0 +1 @@
+mtas_macro::mod_flat!(adb, client);
@@ -0,0 +1,216 @@
+use std::{
+    pin::Pin,
+    sync::mpsc,
+    task::{Context, Poll},
+    thread,
+};
+
+use tokio::sync::oneshot;
+use tokio_stream::Stream;
+use tracing::debug;
+
+use crate::{
+    Command, ControllerError, FrameHub, FrameSubscriber, Platform, Return, ScreenCapture,
+    SharedFrame, SubscriberConfig, controller,
+};
+
+struct Job {
+    command: Command,
+    reply: oneshot::Sender<Result<Return, ControllerError>>,
+}
+
+/// A [`crate::Controller`] running on its own thread, driven from async code.
+///
+/// Commands run one at a time in the order they were sent. Dropping the future of a command
+/// that has not started yet cancels it, a command that already started runs to the end.
+/// The backend is dropped, releasing held touches, once every clone of the handle is gone.
+#[derive(Clone)]
+pub struct AsyncController {
+    jobs: mpsc::Sender<Job>,
+    hub: FrameHub,
+}
+
+impl AsyncController {
+    /// Connect to `platform` on a new thread.
+    pub async fn spawn(platform: Platform) -> Result<(Self, ScreenCapture), ControllerError> {
+        let (ready, connected) = oneshot::channel();
+        let (jobs, pending) = mpsc::channel::<Job>();
+
+        thread::spawn(move || {
+            let mut controller = match controller(platform) {
+                Ok((controller, screen_capture)) => {
+                    if ready.send(Ok(screen_capture)).is_err() {
+                        return;
+                    }
+                    controller
+                }
+                Err(e) => {
+                    let _ = ready.send(Err(e));
+                    return;
+                }
+            };
+
+            for job in pending {
+                if job.reply.is_closed() {
+                    debug!("Skipping cancelled command {:?}", job.command);
+                    continue;
+                }
+                let _ = job.reply.send(controller.execute(job.command));
+            }
+        });
+
+        let screen_capture = connected.await.map_err(|_| ControllerError::Closed)??;
+        let hub = screen_capture.hub().clone();
+
+        Ok((AsyncController { jobs, hub }, screen_capture))
+    }
+
+    pub async fn execute(&self, command: Command) -> Result<Return, ControllerError> {
+        let (reply, result) = oneshot::channel();
+        self.jobs
+            .send(Job { command, reply })
+            .map_err(|_| ControllerError::Closed)?;
+        result.await.map_err(|_| ControllerError::Closed)?
+    }
+
+    /// Captured frames as they are published, rate limited by `config`.
+    ///
+    /// A consumer slower than capture skips to the newest frame instead of falling behind,
+    /// `config.history` has no effect here. The stream ends once capture stops, which happens
+    /// when the controller is dropped.
+    pub fn frames(&self, config: SubscriberConfig) -> impl Stream<Item = SharedFrame> + use<> {
+        self.hub.subscribe(config)
+    }
+}
+
+impl Stream for FrameSubscriber {
+    type Item = SharedFrame;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<SharedFrame>> {
+        FrameSubscriber::poll_next(self.get_mut(), cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::pin, time::Duration};
+
+    use anyhow::{Result, anyhow};
+    use image::{Rgba, RgbaImage};
+    use tokio::time::{sleep, timeout};
+    use tokio_stream::StreamExt;
+
+    use super::*;
+    use crate::mock::MockConfig;
+
+    fn mock() -> MockConfig {
+        let mut config =
+            MockConfig::from_images(vec![RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 255]))]);
+        config.frame_interval = Duration::from_millis(1);
+        config
+    }
+
+    #[tokio::test]
+    async fn test_async_execute() -> Result<()> {
+        let config = mock();
+        let log = config.log.clone();
+
+        let (controller, _screen_cap) = AsyncController::spawn(Platform::Mock(config)).await?;
+        let other = controller.clone();
+
+        let (first, second) = tokio::join!(
+            controller.execute(Command::Tab { x: 1, y: 1 }),
+            other.execute(Command::Text("hi".to_string())),
+        );
+        assert!(matches!(first?, Return::Nothing));
+        assert!(matches!(second?, Return::Nothing));
+        assert_eq!(log.commands().len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_frame_stream() -> Result<()> {
+        let (controller, _screen_cap) = AsyncController::spawn(Platform::Mock(mock())).await?;
+        let mut frames = pin!(controller.frames(SubscriberConfig::default()));
+
+        controller
+            .execute(Command::ControlScreenCapture { start: true })
+            .await?;
+
+        let mut seqs = Vec::new();
+        while seqs.len() < 3 {
+            let frame = timeout(Duration::from_secs(2), frames.next())
+                .await?
+                .ok_or_else(|| anyhow!("frame stream ended"))?;
+            assert_eq!(frame.data[..4], [1, 2, 3, 255]);
+            seqs.push(frame.meta.seq);
+        }
+        assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_frame_stream_ends() -> Result<()> {
+        let (controller, _screen_cap) = AsyncController::spawn(Platform::Mock(mock())).await?;
+        let mut frames = pin!(controller.frames(SubscriberConfig::default()));
+
+        controller
+            .execute(Command::ControlScreenCapture { start: true })
+            .await?;
+        timeout(Duration::from_secs(2), frames.next())
+            .await?
+            .ok_or_else(|| anyhow!("frame stream ended early"))?;
+
+        drop(controller);
+
+        // Frames published before the drop may still be handed out, then the stream ends
+        timeout(Duration::from_secs(2), async {
+            while frames.next().await.is_some() {}
+        })
+        .await
+        .map_err(|_| anyhow!("frame stream still open after the controller was dropped"))?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_cancel() -> Result<()> {
+        let config = mock();
+        let log = config.log.clone();
+
+        let (controller, _screen_cap) = AsyncController::spawn(Platform::Mock(config)).await?;
+
+        let busy = controller.clone();
+        let press = tokio::spawn(async move {
+            busy.execute(Command::LongPress {
+                x: 0,
+                y: 0,
+                hold: Duration::from_millis(100),
+            })
+            .await
+        });
+        sleep(Duration::from_millis(20)).await;
+
+        // Queued behind the long press and dropped before it runs
+        tokio::select! {
+            _ = controller.execute(Command::Tab { x: 9, y: 9 }) => {
+                return Err(anyhow!("tab ran during the long press"));
+            }
+            _ = sleep(Duration::from_millis(10)) => {}
+        }
+
+        press.await??;
+        controller
+            .execute(Command::Text("after".to_string()))
+            .await?;
+
+        let commands = log.commands();
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(commands[0], Command::LongPress { .. }));
+        assert!(matches!(&commands[1], Command::Text(text) if text == "after"));
+
+        Ok(())
+    }
+}
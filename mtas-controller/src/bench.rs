@@ -0,0 +1,419 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+use tracing::*;
+
+use crate::{
+    Command, Controller, ControllerError, Frame, FrameSubscriber, Region, ScreenCapture,
+    SharedFrame, SubscriberConfig,
+};
+
+/// Distribution of a set of durations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    /// Standard deviation.
+    pub jitter: Duration,
+    /// [`Summary::BUCKETS`] equal buckets from `min` to `max`, as upper bound and samples.
+    pub histogram: Vec<(Duration, usize)>,
+}
+
+impl Summary {
+    pub const BUCKETS: usize = 10;
+
+    /// `None` without samples.
+    pub fn new(samples: &[Duration]) -> Option<Summary> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let n = sorted.len();
+
+        // Nearest rank
+        let percentile = |p: f64| sorted[((p * n as f64).ceil() as usize).clamp(1, n) - 1];
+
+        let mean = sorted.iter().sum::<Duration>() / n as u32;
+        let variance = sorted
+            .iter()
+            .map(|s| (s.as_secs_f64() - mean.as_secs_f64()).powi(2))
+            .sum::<f64>()
+            / n as f64;
+
+        let (min, max) = (sorted[0], sorted[n - 1]);
+        let width = (max - min) / Self::BUCKETS as u32;
+        let mut histogram: Vec<_> = (1..=Self::BUCKETS)
+            .map(|i| (min + width * i as u32, 0))
+            .collect();
+        histogram[Self::BUCKETS - 1].0 = max;
+        for sample in &sorted {
+            let bucket = match width.as_nanos() {
+                0 => 0,
+                width => ((*sample - min).as_nanos() / width) as usize,
+            };
+            histogram[bucket.min(Self::BUCKETS - 1)].1 += 1;
+        }
+
+        Some(Summary {
+            count: n,
+            min,
+            mean,
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max,
+            jitter: Duration::from_secs_f64(variance.sqrt()),
+            histogram,
+        })
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "p50 {:?}  p90 {:?}  p99 {:?}  max {:?}  jitter {:?}  ({} samples)",
+            self.p50, self.p90, self.p99, self.max, self.jitter, self.count
+        )?;
+
+        let widest = self.histogram.iter().map(|(_, n)| *n).max().unwrap_or(0);
+        for (upper, n) in &self.histogram {
+            let bar = (n * 40).div_ceil(widest.max(1));
+            writeln!(f, "  <= {:>12?} {:>5} {}", upper, n, "#".repeat(bar))?;
+        }
+        Ok(())
+    }
+}
+
+/// Tap and wait until the screen shows it, e.g. with Android's show taps option. A tap works
+/// on every backend, adb without minitouch included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundTripConfig {
+    pub x: i32,
+    pub y: i32,
+    pub samples: usize,
+    /// Only changes inside this part of the capture count, the whole frame if `None`.
+    pub region: Option<Region>,
+}
+
+/// Execute a command over and over.
+#[derive(Clone, Debug)]
+pub struct ThroughputConfig {
+    pub command: Command,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct BenchConfig {
+    /// Frames sampled for capture latency and frame interval.
+    pub frames: usize,
+    /// Longest wait for any one frame.
+    pub timeout: Duration,
+    pub round_trip: Option<RoundTripConfig>,
+    pub throughput: Option<ThroughputConfig>,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        BenchConfig {
+            frames: 100,
+            timeout: Duration::from_secs(2),
+            round_trip: None,
+            throughput: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BenchReport {
+    /// Time each capture took.
+    pub capture: Option<Summary>,
+    /// Time between consecutive frames.
+    pub interval: Option<Summary>,
+    /// From sending a tap to the end of the first capture showing it.
+    pub round_trip: Option<Summary>,
+    /// Time each throughput command took.
+    pub command: Option<Summary>,
+    /// Throughput commands per second.
+    pub throughput: Option<f64>,
+}
+
+impl Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sections = [
+            ("Capture latency", &self.capture),
+            ("Frame interval", &self.interval),
+            ("Input round trip", &self.round_trip),
+            ("Command latency", &self.command),
+        ];
+        for (name, summary) in sections {
+            if let Some(summary) = summary {
+                write!(f, "{name}: {summary}")?;
+            }
+        }
+        if let Some(throughput) = self.throughput {
+            writeln!(f, "Throughput: {throughput:.1} commands/s")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BenchError {
+    #[error("Controller Error occurred: {0}")]
+    Controller(#[from] ControllerError),
+
+    #[error("No Frame Within {0:?}")]
+    NoFrame(Duration),
+
+    #[error("Screen Did Not Change Within {0:?} After Tap")]
+    NoChange(Duration),
+}
+
+/// Measure `controller` and its capture, starting capture if it was not running.
+pub fn run_benchmark(
+    controller: &mut Controller,
+    screen_cap: &ScreenCapture,
+    config: &BenchConfig,
+) -> Result<BenchReport, BenchError> {
+    controller.execute(Command::ControlScreenCapture { start: true })?;
+    // History keeps the frames `next` skips, a tap may show on a few of them only
+    let mut subscriber = screen_cap.subscribe(SubscriberConfig {
+        history: 16,
+        ..Default::default()
+    });
+
+    let (capture, interval) = sample_frames(&mut subscriber, config)?;
+    let mut report = BenchReport {
+        capture: Summary::new(&capture),
+        interval: Summary::new(&interval),
+        ..Default::default()
+    };
+
+    if let Some(round_trip_config) = &config.round_trip {
+        let samples = round_trip(
+            controller,
+            &mut subscriber,
+            round_trip_config,
+            config.timeout,
+        )?;
+        report.round_trip = Summary::new(&samples);
+    }
+
+    if let Some(throughput) = &config.throughput {
+        let start = Instant::now();
+        let mut samples = Vec::with_capacity(throughput.count);
+        for _ in 0..throughput.count {
+            let sent = Instant::now();
+            controller.execute(throughput.command.clone())?;
+            samples.push(sent.elapsed());
+        }
+        report.throughput = Some(throughput.count as f64 / start.elapsed().as_secs_f64());
+        report.command = Summary::new(&samples);
+    }
+
+    info!("Benchmark finished");
+    Ok(report)
+}
+
+fn sample_frames(
+    subscriber: &mut FrameSubscriber,
+    config: &BenchConfig,
+) -> Result<(Vec<Duration>, Vec<Duration>), BenchError> {
+    let mut capture = Vec::with_capacity(config.frames);
+    let mut interval = Vec::with_capacity(config.frames);
+    let mut last: Option<(u64, Instant)> = None;
+
+    while capture.len() < config.frames {
+        let frame = subscriber
+            .next(config.timeout)
+            .ok_or(BenchError::NoFrame(config.timeout))?;
+        let meta = &frame.meta;
+        capture.push(meta.finished.saturating_duration_since(meta.started));
+
+        // Skipped frames would count as one long interval
+        if let Some((seq, finished)) = last
+            && meta.seq == seq + 1
+        {
+            interval.push(meta.finished.saturating_duration_since(finished));
+        }
+        last = Some((meta.seq, meta.finished));
+    }
+
+    Ok((capture, interval))
+}
+
+fn round_trip(
+    controller: &mut Controller,
+    subscriber: &mut FrameSubscriber,
+    config: &RoundTripConfig,
+    timeout: Duration,
+) -> Result<Vec<Duration>, BenchError> {
+    let mut samples = Vec::with_capacity(config.samples);
+
+    for _ in 0..config.samples {
+        let baseline = subscriber
+            .next(timeout)
+            .ok_or(BenchError::NoFrame(timeout))?;
+
+        let sent = Instant::now();
+        controller.execute(Command::Tab {
+            x: config.x,
+            y: config.y,
+        })?;
+        let changed = wait_for(subscriber, sent, timeout, |frame| {
+            differs(frame, &baseline, config.region)
+        })
+        .ok_or(BenchError::NoChange(timeout))?;
+        samples.push(changed.meta.finished.saturating_duration_since(sent));
+
+        // Let the screen go back before the next tap, a lingering change is not fatal
+        let shown = Instant::now();
+        wait_for(subscriber, shown, timeout, |frame| {
+            !differs(frame, &baseline, config.region)
+        });
+    }
+
+    Ok(samples)
+}
+
+/// First frame captured after `after` that passes `pred`, looking at the skipped ones too.
+fn wait_for(
+    subscriber: &mut FrameSubscriber,
+    after: Instant,
+    timeout: Duration,
+    pred: impl Fn(&Frame) -> bool,
+) -> Option<SharedFrame> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        subscriber.next(deadline.saturating_duration_since(Instant::now()))?;
+        let found = subscriber
+            .take_history()
+            .into_iter()
+            .find(|frame| frame.meta.started >= after && pred(frame));
+        if found.is_some() {
+            return found;
+        }
+    }
+}
+
+fn differs(a: &Frame, b: &Frame, region: Option<Region>) -> bool {
+    if (a.width(), a.height()) != (b.width(), b.height()) {
+        return true;
+    }
+    let region = region.unwrap_or(Region::new(0, 0, a.width(), a.height()));
+    a.region_rows(region).ne(b.region_rows(region))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Result, anyhow};
+    use image::{Rgba, RgbaImage};
+    use mtas_utils::Affine;
+
+    use super::*;
+    use crate::mock::MockConfig;
+    use crate::{Platform, controller};
+
+    #[test]
+    fn test_summary() -> Result<()> {
+        let samples: Vec<_> = (1..=100).rev().map(Duration::from_millis).collect();
+        let summary = Summary::new(&samples).ok_or(anyhow!("no summary"))?;
+
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.mean, Duration::from_micros(50_500));
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p90, Duration::from_millis(90));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        assert_eq!(summary.max, Duration::from_millis(100));
+        assert!(summary.histogram.iter().all(|(_, n)| *n == 10));
+        assert_eq!(summary.histogram.last().map(|b| b.0), Some(summary.max));
+
+        let flat = Summary::new(&[Duration::from_millis(3); 4]).ok_or(anyhow!("no summary"))?;
+        assert_eq!(flat.jitter, Duration::ZERO);
+        assert_eq!(flat.histogram[0].1, 4);
+
+        assert_eq!(Summary::new(&[]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_benchmark_mock() -> Result<()> {
+        let mut config =
+            MockConfig::from_images(vec![RgbaImage::from_pixel(16, 16, Rgba([0, 0, 0, 255]))]);
+        config.frame_interval = Duration::from_millis(4);
+        config.command_delay = Duration::from_millis(2);
+        config.touch_feedback = Some(Affine::IDENTITY);
+        let (mut controller, screen_cap) = controller(Platform::Mock(config))?;
+
+        let report = run_benchmark(
+            &mut controller,
+            &screen_cap,
+            &BenchConfig {
+                frames: 20,
+                timeout: Duration::from_secs(2),
+                round_trip: Some(RoundTripConfig {
+                    x: 8,
+                    y: 8,
+                    samples: 3,
+                    region: Some(Region::new(4, 4, 8, 8)),
+                }),
+                throughput: Some(ThroughputConfig {
+                    command: Command::Text("x".to_string()),
+                    count: 10,
+                }),
+            },
+        )?;
+
+        let summary = |s: &Option<Summary>| s.clone().ok_or(anyhow!("missing summary"));
+        assert_eq!(summary(&report.capture)?.count, 20);
+        assert!(summary(&report.capture)?.p50 >= Duration::from_millis(4));
+        assert!(summary(&report.interval)?.p50 >= Duration::from_millis(4));
+        assert_eq!(summary(&report.round_trip)?.count, 3);
+        assert!(summary(&report.round_trip)?.min >= Duration::from_millis(2));
+        assert!(summary(&report.command)?.min >= Duration::from_millis(2));
+        assert!(report.throughput.is_some_and(|t| t > 0.0 && t <= 500.0));
+        assert!(report.to_string().contains("Input round trip: p50"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_no_change() -> Result<()> {
+        let mut config =
+            MockConfig::from_images(vec![RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]))]);
+        config.frame_interval = Duration::from_millis(1);
+        let (mut controller, screen_cap) = controller(Platform::Mock(config))?;
+
+        let result = run_benchmark(
+            &mut controller,
+            &screen_cap,
+            &BenchConfig {
+                frames: 2,
+                timeout: Duration::from_millis(50),
+                round_trip: Some(RoundTripConfig {
+                    x: 1,
+                    y: 1,
+                    samples: 1,
+                    region: None,
+                }),
+                throughput: None,
+            },
+        );
+        assert!(matches!(result, Err(BenchError::NoChange(_))));
+
+        Ok(())
+    }
+}
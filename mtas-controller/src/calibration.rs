@@ -0,0 +1,365 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use mtas_utils::{Affine, Size};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::*;
+
+use crate::{Command, Controller, ControllerError, Frame, InputSpace, ScreenCapture, SharedFrame};
+
+/// How [`calibrate`] probes the device.
+#[derive(Clone, Debug)]
+pub struct CalibrationConfig {
+    /// Input resolution in the natural orientation, the capture resolution when `None`.
+    pub input: Option<Size>,
+    /// Where to touch, as fractions of the input space. At least three, not all on one line.
+    pub targets: Vec<(f64, f64)>,
+    /// How long the screen is left alone after a tap, so its marker is gone before the next.
+    pub settle: Duration,
+    /// Longest wait for a fresh frame.
+    pub timeout: Duration,
+    /// Smallest change of a color channel that counts as the touch marker.
+    pub threshold: u8,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        CalibrationConfig {
+            input: None,
+            targets: vec![(0.2, 0.2), (0.8, 0.2), (0.2, 0.8), (0.8, 0.8), (0.5, 0.5)],
+            settle: Duration::from_millis(500),
+            timeout: Duration::from_secs(2),
+            threshold: 64,
+        }
+    }
+}
+
+/// Measured mapping from capture pixels to input pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    /// Input resolution the transform was measured for.
+    pub input: Size,
+    pub transform: Affine,
+    /// Distance left between the touched points and the fitted ones, in input pixels.
+    pub rms_error: f64,
+}
+
+#[derive(Error, Debug)]
+pub enum CalibrationError {
+    #[error("Controller Error occurred: {0}")]
+    Controller(#[from] ControllerError),
+
+    #[error("No Frame Captured Within {0:?}")]
+    NoFrame(Duration),
+
+    #[error("Touch At ({x}, {y}) Not Seen In The Captured Frames")]
+    NotDetected { x: i32, y: i32 },
+
+    #[error("Calibration Points Do Not Span The Screen")]
+    Degenerate,
+
+    #[error("Calibration Profile Io Failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid Calibration Profile {path:?}: {reason}")]
+    InvalidProfile { path: PathBuf, reason: String },
+}
+
+impl Calibration {
+    /// What `Command::SetInputSpace` takes to apply the calibration.
+    pub fn input_space(&self) -> InputSpace {
+        InputSpace {
+            size: Some(self.input),
+            calibration: Some(self.transform),
+            ..Default::default()
+        }
+    }
+}
+
+/// Tap every target and find it in the captured frames, then fit capture to input pixels.
+///
+/// The device has to show where it is tapped, e.g. with Android's show taps option
+/// (`adb shell settings put system show_touches 1`), on an otherwise still screen. Taps work
+/// on every backend, adb without minitouch included.
+/// The controller is left with an uncalibrated input space of the probed size, apply
+/// [`Calibration::input_space`] afterwards.
+pub fn calibrate(
+    controller: &mut Controller,
+    screen_cap: &mut ScreenCapture,
+    config: &CalibrationConfig,
+) -> Result<Calibration, CalibrationError> {
+    controller.execute(Command::ControlScreenCapture { start: true })?;
+
+    let first = frame_after(screen_cap, Instant::now(), config.timeout)?;
+    let capture = Size::new(first.width() as u32, first.height() as u32);
+    let input = config.input.unwrap_or(capture);
+
+    // Probes are in input pixels, held to that size instead of a calibration from before
+    controller.execute(Command::SetInputSpace(InputSpace {
+        size: Some(input),
+        ..Default::default()
+    }))?;
+
+    let mut pairs = Vec::with_capacity(config.targets.len());
+    for &(fx, fy) in &config.targets {
+        let x = (fx * input.width.saturating_sub(1) as f64).round() as i32;
+        let y = (fy * input.height.saturating_sub(1) as f64).round() as i32;
+
+        let before = frame_after(screen_cap, Instant::now(), config.timeout)?;
+        controller.execute(Command::Tab { x, y })?;
+        let after = frame_after(screen_cap, Instant::now(), config.timeout)?;
+
+        let seen = detect_touch(&before, &after, config.threshold)
+            .ok_or(CalibrationError::NotDetected { x, y })?;
+        debug!("Touch at ({}, {}) seen at {:?}", x, y, seen);
+        pairs.push((seen, (x as f64, y as f64)));
+
+        std::thread::sleep(config.settle);
+    }
+
+    let transform = Affine::fit(&pairs).ok_or(CalibrationError::Degenerate)?;
+    let calibration = Calibration {
+        input,
+        transform,
+        rms_error: transform.rms_error(&pairs),
+    };
+    info!(
+        "Calibrated {:?} to {:?}, error {:.2}px",
+        capture, input, calibration.rms_error
+    );
+
+    Ok(calibration)
+}
+
+/// First frame whose capture started at or after `after`.
+fn frame_after(
+    screen_cap: &mut ScreenCapture,
+    after: Instant,
+    timeout: Duration,
+) -> Result<SharedFrame, CalibrationError> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let frame = screen_cap.latest();
+        if frame.meta.seq > 0 && frame.meta.started >= after {
+            return Ok(frame);
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(CalibrationError::NoFrame(timeout));
+        }
+        screen_cap.wait_new_frame(deadline - now);
+    }
+}
+
+/// Center of the pixels that changed between the frames, `None` if nothing or too much did.
+pub fn detect_touch(before: &Frame, after: &Frame, threshold: u8) -> Option<(f64, f64)> {
+    if (before.width(), before.height()) != (after.width(), after.height()) {
+        return None;
+    }
+
+    let width = after.width();
+    let (mut count, mut sum_x, mut sum_y) = (0usize, 0.0, 0.0);
+    for (i, (a, b)) in before
+        .data
+        .chunks_exact(4)
+        .zip(after.data.chunks_exact(4))
+        .enumerate()
+    {
+        if a[..3]
+            .iter()
+            .zip(&b[..3])
+            .any(|(a, b)| a.abs_diff(*b) >= threshold)
+        {
+            count += 1;
+            sum_x += (i % width) as f64;
+            sum_y += (i / width) as f64;
+        }
+    }
+
+    // A marker is small, a large change means the screen itself moved
+    if count == 0 || count > width * after.height() / 20 {
+        return None;
+    }
+    Some((sum_x / count as f64, sum_y / count as f64))
+}
+
+/// Calibrations saved by device profile name, one small JSON file each.
+#[derive(Clone, Debug)]
+pub struct CalibrationStore {
+    dir: PathBuf,
+}
+
+impl CalibrationStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        CalibrationStore { dir: dir.into() }
+    }
+
+    /// File of `profile`, characters that are not safe in file names become `_`.
+    pub fn path(&self, profile: &str) -> PathBuf {
+        let name: String = profile
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{name}.json"))
+    }
+
+    pub fn save(
+        &self,
+        profile: &str,
+        calibration: &Calibration,
+    ) -> Result<PathBuf, CalibrationError> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(profile);
+        let json = serde_json::to_string_pretty(calibration).map_err(std::io::Error::from)?;
+        std::fs::write(&path, json)?;
+        Ok(path)
+    }
+
+    /// `None` if `profile` was never calibrated.
+    pub fn load(&self, profile: &str) -> Result<Option<Calibration>, CalibrationError> {
+        let path = self.path(profile);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| CalibrationError::InvalidProfile {
+                path,
+                reason: e.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Result, anyhow};
+    use image::{Rgba, RgbaImage};
+    use mtas_utils::CapturePoint;
+
+    use super::*;
+    use crate::mock::{MARKER_FADE, MockConfig};
+    use crate::{Platform, controller, touch::TouchEvent};
+
+    #[test]
+    fn test_calibrate_mock() -> Result<()> {
+        // Portrait panel of 720x1280 shown turned clockwise at a quarter of its size
+        let feedback = Affine {
+            a: 0.0,
+            b: -0.25,
+            c: 319.0,
+            d: 0.25,
+            e: 0.0,
+            f: 0.0,
+        };
+        let mut config = MockConfig::from_images(vec![RgbaImage::from_pixel(
+            320,
+            180,
+            Rgba([40, 40, 40, 255]),
+        )]);
+        config.frame_interval = Duration::from_millis(1);
+        config.touch_feedback = Some(feedback);
+        let log = config.log.clone();
+
+        let (mut controller, mut screen_cap) = controller(Platform::Mock(config))?;
+        let calibration = calibrate(
+            &mut controller,
+            &mut screen_cap,
+            &CalibrationConfig {
+                input: Some(Size::new(720, 1280)),
+                settle: MARKER_FADE * 2,
+                ..Default::default()
+            },
+        )?;
+
+        // Probed with plain taps, which adb without minitouch can send too
+        let taps = log
+            .commands()
+            .iter()
+            .filter(|c| matches!(c, Command::Tab { .. }))
+            .count();
+        assert_eq!(taps, CalibrationConfig::default().targets.len());
+        assert!(log.touches().chunks(2).all(|pair| matches!(
+            pair,
+            [TouchEvent::Down { .. }, TouchEvent::Up { .. }]
+        )));
+
+        // Markers land on whole capture pixels, a quarter of the input resolution
+        assert!(calibration.rms_error < 3.0);
+        let inverse = feedback.inverse().unwrap();
+        let (x, y) = calibration.transform.apply(160.0, 90.0);
+        let (ex, ey) = inverse.apply(160.0, 90.0);
+        assert!((x - ex).abs() < 3.0 && (y - ey).abs() < 3.0);
+
+        controller.execute(Command::SetInputSpace(calibration.input_space()))?;
+        log.clear();
+        controller.execute(Command::TabAt(CapturePoint::new(160, 90).into()))?;
+        let TouchEvent::Down { x, y, .. } = log.touches()[0] else {
+            return Err(anyhow!("expected a touch down"));
+        };
+        assert!((x as f64 - ex).abs() < 3.0 && (y as f64 - ey).abs() < 3.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calibrate_without_feedback() -> Result<()> {
+        let mut config =
+            MockConfig::from_images(vec![RgbaImage::from_pixel(32, 32, Rgba([0, 0, 0, 255]))]);
+        config.frame_interval = Duration::from_millis(1);
+
+        let (mut controller, mut screen_cap) = controller(Platform::Mock(config))?;
+        let result = calibrate(
+            &mut controller,
+            &mut screen_cap,
+            &CalibrationConfig {
+                settle: Duration::from_millis(5),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(CalibrationError::NotDetected { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_round_trip() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("mtas-calibration-{}", std::process::id()));
+        let store = CalibrationStore::new(&dir);
+        let calibration = Calibration {
+            input: Size::new(1080, 1920),
+            transform: Affine {
+                c: 3.5,
+                ..Affine::IDENTITY
+            },
+            rms_error: 0.25,
+        };
+
+        assert_eq!(store.load("127.0.0.1:5555")?, None);
+        let path = store.save("127.0.0.1:5555", &calibration)?;
+        assert!(path.ends_with("127.0.0.1_5555.json"));
+        assert_eq!(store.load("127.0.0.1:5555")?, Some(calibration));
+
+        std::fs::write(&path, r#"{"input":{"width":1,"height":2}}"#)?;
+        assert!(matches!(
+            store.load("127.0.0.1:5555"),
+            Err(CalibrationError::InvalidProfile { .. })
+        ));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
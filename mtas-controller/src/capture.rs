@@ -0,0 +1,814 @@
+use std::{
+    fmt::Display,
+    sync::{
+        Arc, Condvar, Mutex,
+        mpsc::{Receiver, RecvTimeoutError, SendError, Sender, TryRecvError},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use ringbuf::{
+    HeapCons, HeapRb,
+    traits::{Consumer, Producer, Split},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use triple_buffer::triple_buffer;
+
+use tracing::*;
+
+use mtas_utils::Size;
+
+use crate::{
+    Frame, FrameHub, FramePool, PixelFormat, PooledFrame, Region, Return, RoiState, ScreenCapture,
+    SharedFrame,
+};
+
+pub enum ScreenCapCommand {
+    CaptureEnabled(bool),
+    CaptureTimingEnabled(bool),
+    FrameRate(FrameRate),
+}
+
+/// How often the capture thread grabs a frame while capture is enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FrameRate {
+    /// As fast as the backend delivers, keeps one core busy.
+    Unlimited,
+    /// At most this many frames a second.
+    Fixed(f64),
+    /// At most `max` frames a second, and only once the previous frame has been read.
+    Adaptive { max: f64 },
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        FrameRate::Fixed(60.0)
+    }
+}
+
+impl FrameRate {
+    fn interval(&self) -> Duration {
+        match *self {
+            FrameRate::Unlimited => Duration::ZERO,
+            FrameRate::Fixed(fps) | FrameRate::Adaptive { max: fps } => {
+                Duration::from_secs_f64(1.0 / fps.max(0.1))
+            }
+        }
+    }
+}
+
+/// State the capture thread shares with [`ScreenCapture`].
+pub(crate) struct CaptureShared {
+    pub(crate) stats: Mutex<CaptureStats>,
+    /// Notified after every publish.
+    pub(crate) published: Condvar,
+    pub(crate) rois: Mutex<RoiState>,
+    /// Full and partial frames alike, what ROI leases read.
+    pub(crate) roi_hub: FrameHub,
+    /// Bumped when a frame is read or a command is sent, an adaptive capture sleeps on it.
+    pub(crate) wakeups: Mutex<u64>,
+    pub(crate) woken: Condvar,
+}
+
+impl CaptureShared {
+    pub(crate) fn wake(&self) {
+        *self.wakeups.lock().unwrap() += 1;
+        self.woken.notify_all();
+    }
+}
+
+/// Capture thread counters, read with [`ScreenCapture::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CaptureStats {
+    pub enabled: bool,
+    pub target: FrameRate,
+    /// Measured frames per second, 0 while disabled.
+    pub fps: f64,
+    /// `seq` of the newest full frame, frames captured so far with ROI captures counted.
+    pub frames: u64,
+    pub failures: u64,
+    /// Time the last successful capture took.
+    pub capture_time: Duration,
+}
+
+impl CaptureStats {
+    /// Weight of the newest frame interval in the smoothed `fps`.
+    const SMOOTHING: f64 = 0.1;
+
+    fn frame(&mut self, seq: u64, interval: Option<Duration>, capture_time: Duration) {
+        self.frames = seq;
+        self.capture_time = capture_time;
+
+        if let Some(interval) = interval.filter(|i| !i.is_zero()) {
+            let fps = 1.0 / interval.as_secs_f64();
+            self.fps = if self.fps == 0.0 {
+                fps
+            } else {
+                self.fps + (fps - self.fps) * Self::SMOOTHING
+            };
+        }
+    }
+}
+
+/// Capture thread status, read with [`ScreenCapture::try_event`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaptureEvent {
+    /// The screen changed size or rotated, later frames have the new size.
+    Resized { width: usize, height: usize },
+    /// A capture failed, the next attempt is made after `retry_in`.
+    Failed { error: String, retry_in: Duration },
+    /// A capture succeeded after failures.
+    Recovered,
+}
+
+/// A backend specific way of grabbing one RGBA frame.
+///
+/// Implementors only need to fill the frame, the capture thread, triple buffer and
+/// timing ring are shared by every backend through [`spawn_capture`].
+pub trait FrameSource: Send + 'static {
+    type Error: Display;
+
+    /// Fill `frame` with the current screen, resizing it first if the screen size changed.
+    fn capture(&mut self, frame: &mut Frame) -> Result<(), Self::Error>;
+
+    /// Pixel format `capture` writes, converted to RGBA before publishing.
+    fn format(&self) -> PixelFormat {
+        PixelFormat::Rgba8
+    }
+
+    /// Whether `capture` writes the bottom row first, flipped before publishing.
+    fn bottom_up(&self) -> bool {
+        false
+    }
+
+    /// Fill `frame` with only `region` of the screen, for backends that can do so cheaper
+    /// than a full capture. `Ok(false)` if they can't, a full frame is captured instead.
+    fn capture_region(&mut self, _frame: &mut Frame, _region: Region) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+/// `Command::TestScreenShotDelay` got no timings in time, capture kept failing.
+#[derive(Error, Debug)]
+#[error("No Capture Succeeded Within {0:?} To Time")]
+pub struct CaptureTimeout(pub Duration);
+
+/// Exponential retry delay of a failing capture.
+struct Backoff {
+    next: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    const INITIAL: Duration = Duration::from_millis(10);
+    const MAX: Duration = Duration::from_secs(2);
+
+    fn new() -> Self {
+        Backoff {
+            next: Self::INITIAL,
+            failures: 0,
+        }
+    }
+
+    fn fail(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(Self::MAX);
+        self.failures += 1;
+        delay
+    }
+
+    /// Returns whether there were failures to recover from.
+    fn succeed(&mut self) -> bool {
+        let recovered = self.failures > 0;
+        *self = Backoff::new();
+        recovered
+    }
+}
+
+struct CaptureState {
+    enabled: bool,
+    timing: bool,
+    rate: FrameRate,
+    shared: Arc<CaptureShared>,
+}
+
+impl CaptureState {
+    fn apply(&mut self, command: ScreenCapCommand) {
+        match command {
+            ScreenCapCommand::CaptureEnabled(on) => self.enabled = on,
+            ScreenCapCommand::CaptureTimingEnabled(on) => self.timing = on,
+            ScreenCapCommand::FrameRate(rate) => self.rate = rate,
+        }
+
+        let mut stats = self.shared.stats.lock().unwrap();
+        stats.enabled = self.enabled;
+        stats.target = self.rate;
+        if !self.enabled {
+            stats.fps = 0.0;
+        }
+    }
+
+    /// Take commands until `deadline`, returns false once the controller is gone.
+    fn wait_until(&mut self, commands: &Receiver<ScreenCapCommand>, deadline: Instant) -> bool {
+        loop {
+            // Checks the channel even when capture overran the deadline, so a closed one is seen
+            match commands.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(command) => self.apply(command),
+                Err(RecvTimeoutError::Timeout) => return true,
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+    }
+}
+
+/// Controller side handle of a capture thread started by [`spawn_capture`].
+pub struct CaptureHandle {
+    screen_cmdtx: Sender<ScreenCapCommand>,
+    shared: Arc<CaptureShared>,
+    cons: HeapCons<Duration>,
+    hub: FrameHub,
+    /// Size passed to [`spawn_capture`], used until the first frame is published.
+    size: Size,
+    thread: Option<JoinHandle<()>>,
+}
+
+pub fn spawn_capture<S: FrameSource>(
+    mut source: S,
+    width: usize,
+    height: usize,
+) -> (CaptureHandle, ScreenCapture) {
+    let (mut input_buffer, output_buffer) = triple_buffer(&SharedFrame::new(
+        PooledFrame::unpooled(Frame::new(width, height)),
+    ));
+    let pool = FramePool::default();
+    let hub = FrameHub::new();
+
+    let (screen_cmdtx, screen_cmdrx) = std::sync::mpsc::channel::<ScreenCapCommand>();
+    let (eventtx, eventrx) = std::sync::mpsc::channel::<CaptureEvent>();
+    let shared = Arc::new(CaptureShared {
+        stats: Mutex::new(CaptureStats::default()),
+        published: Condvar::new(),
+        rois: Mutex::new(RoiState::new((width, height))),
+        roi_hub: FrameHub::new(),
+        wakeups: Mutex::new(0),
+        woken: Condvar::new(),
+    });
+
+    let screen_capture = ScreenCapture {
+        width,
+        height,
+        capture: output_buffer,
+        events: eventrx,
+        shared: shared.clone(),
+        hub: hub.clone(),
+    };
+
+    let rb = HeapRb::<Duration>::new(10);
+    let (mut prod, cons) = rb.split();
+
+    let mut handle = CaptureHandle {
+        screen_cmdtx,
+        shared: shared.clone(),
+        cons,
+        hub: hub.clone(),
+        size: Size::new(width as u32, height as u32),
+        thread: None,
+    };
+
+    handle.thread = Some(std::thread::spawn(move || {
+        info!("Thread ScreenCap Begin");
+
+        let mut state = CaptureState {
+            enabled: false,
+            timing: false,
+            rate: FrameRate::default(),
+            shared,
+        };
+        let mut backoff = Backoff::new();
+        let mut size = (width, height);
+        let mut last_publish: Option<Instant> = None;
+        let mut last_full: Option<Instant> = None;
+        let mut seq = 0;
+
+        'capture: loop {
+            // Sleep on the channel while there is nothing to capture
+            if !state.enabled {
+                last_publish = None;
+                match screen_cmdrx.recv() {
+                    Ok(command) => state.apply(command),
+                    Err(_) => break,
+                }
+                continue;
+            }
+
+            let start = Instant::now();
+            let mut frame = pool.get(size.0, size.1);
+
+            // Only what ROI leases need when consumers asked for that, with a full frame now
+            // and then for everyone else
+            let (roi, roi_rate) = {
+                let rois = state.shared.rois.lock().unwrap();
+                let roi = rois.capture_region(size).filter(|_| {
+                    last_full.is_some_and(|full| start - full < RoiState::FULL_INTERVAL)
+                });
+                (roi, rois.rate)
+            };
+            let captured = match roi {
+                Some(region) => source
+                    .capture_region(&mut frame, region)
+                    .map(|native| native.then_some(region)),
+                None => Ok(None),
+            };
+            let captured = match captured {
+                Ok(None) => source.capture(&mut frame).map(|()| None),
+                captured => captured,
+            };
+
+            let region = match captured {
+                Ok(region) => region,
+                Err(e) => {
+                    let retry_in = backoff.fail();
+                    warn!(
+                        "Failed to capture display, retrying in {:?}: {}",
+                        retry_in, e
+                    );
+                    state.shared.stats.lock().unwrap().failures += 1;
+                    let _ = eventtx.send(CaptureEvent::Failed {
+                        error: e.to_string(),
+                        retry_in,
+                    });
+
+                    if !state.wait_until(&screen_cmdrx, start + retry_in) {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            frame.normalize(source.format(), source.bottom_up());
+
+            let finished = Instant::now();
+            let capture_time = finished - start;
+
+            if backoff.succeed() {
+                info!("Capture recovered");
+                let _ = eventtx.send(CaptureEvent::Recovered);
+            }
+
+            seq += 1;
+            frame.meta.seq = seq;
+            frame.meta.started = start;
+            frame.meta.finished = finished;
+
+            if let Some(region) = region {
+                frame.meta.region = region;
+            } else {
+                frame.meta.region = Region::new(0, 0, frame.width(), frame.height());
+                last_full = Some(start);
+
+                // Only full frames tell the display size
+                if (frame.width(), frame.height()) != size {
+                    size = (frame.width(), frame.height());
+                    info!("Display resized to {}x{}", size.0, size.1);
+                    state.shared.rois.lock().unwrap().display = size;
+                    let _ = eventtx.send(CaptureEvent::Resized {
+                        width: size.0,
+                        height: size.1,
+                    });
+                }
+            }
+            frame.meta.display = size;
+
+            if state.timing {
+                let _ = prod.try_push(capture_time);
+            }
+
+            // The frame this replaces goes back to the pool once no consumer holds it
+            let frame = SharedFrame::new(frame);
+            state.shared.roi_hub.publish(frame.clone());
+
+            // Partial frames only go to ROI leases, everyone else keeps seeing the whole screen
+            let partial = region.is_some();
+            if !partial {
+                hub.publish(frame.clone());
+                input_buffer.write(frame);
+
+                let now = Instant::now();
+                state.shared.stats.lock().unwrap().frame(
+                    seq,
+                    last_publish.map(|last| now - last),
+                    capture_time,
+                );
+                state.shared.published.notify_all();
+                last_publish = Some(now);
+            }
+
+            // Partial frames keep their own pace
+            let rate = roi_rate.filter(|_| partial).unwrap_or(state.rate);
+            if !state.wait_until(&screen_cmdrx, start + rate.interval()) {
+                break;
+            }
+
+            // Adaptive: sleep until the consumer has taken the frame or a command arrives
+            while !partial && matches!(state.rate, FrameRate::Adaptive { .. }) && state.enabled {
+                let seen = *state.shared.wakeups.lock().unwrap();
+                if input_buffer.consumed() {
+                    break;
+                }
+                match screen_cmdrx.try_recv() {
+                    Ok(command) => {
+                        state.apply(command);
+                        continue;
+                    }
+                    Err(TryRecvError::Disconnected) => break 'capture,
+                    Err(TryRecvError::Empty) => {}
+                }
+
+                let mut wakeups = state.shared.wakeups.lock().unwrap();
+                while *wakeups == seen {
+                    wakeups = state.shared.woken.wait(wakeups).unwrap();
+                }
+            }
+        }
+
+        hub.close();
+        state.shared.roi_hub.close();
+        info!("Thread ScreenCap End");
+    }));
+
+    (handle, screen_capture)
+}
+
+impl CaptureHandle {
+    /// Stop the capture thread and wait for it to end, so the source is no longer used.
+    /// Backends call this before closing the connection their source reads from.
+    pub fn stop(&mut self) {
+        // Disconnect the channel before waking the thread, so it sees it is time to stop
+        let (closed, _) = std::sync::mpsc::channel();
+        drop(std::mem::replace(&mut self.screen_cmdtx, closed));
+        self.shared.wake();
+
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            warn!("Capture thread panicked");
+        }
+    }
+
+    /// Size of the newest captured frame.
+    pub fn frame_size(&self) -> Size {
+        self.hub.latest().map_or(self.size, |frame| {
+            let (width, height) = frame.meta.display;
+            Size::new(width as u32, height as u32)
+        })
+    }
+
+    pub fn control_screen_capture(
+        &self,
+        start: bool,
+    ) -> Result<Return, SendError<ScreenCapCommand>> {
+        self.send(ScreenCapCommand::CaptureEnabled(start))?;
+
+        Ok(Return::Nothing)
+    }
+
+    pub fn control_screen_capture_timing(
+        &self,
+        start: bool,
+    ) -> Result<Return, SendError<ScreenCapCommand>> {
+        self.send(ScreenCapCommand::CaptureTimingEnabled(start))?;
+
+        Ok(Return::Nothing)
+    }
+
+    pub fn set_frame_rate(&self, rate: FrameRate) -> Result<Return, SendError<ScreenCapCommand>> {
+        self.send(ScreenCapCommand::FrameRate(rate))?;
+
+        Ok(Return::Nothing)
+    }
+
+    fn send(&self, command: ScreenCapCommand) -> Result<(), SendError<ScreenCapCommand>> {
+        self.screen_cmdtx.send(command)?;
+        self.shared.wake();
+        Ok(())
+    }
+
+    /// Longest `Command::TestScreenShotDelay` waits for its timings.
+    pub const SCREEN_SHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn test_screen_shot_delay<E>(&mut self) -> Result<Return, E>
+    where
+        E: From<SendError<ScreenCapCommand>> + From<CaptureTimeout>,
+    {
+        self.screen_shot_delay(Self::SCREEN_SHOT_TIMEOUT)
+    }
+
+    fn screen_shot_delay<E>(&mut self, timeout: Duration) -> Result<Return, E>
+    where
+        E: From<SendError<ScreenCapCommand>> + From<CaptureTimeout>,
+    {
+        self.control_screen_capture(true)?;
+        self.control_screen_capture_timing(true)?;
+
+        let deadline = Instant::now() + timeout;
+        let mut times = Vec::with_capacity(10);
+        while times.len() < 10 {
+            if let Some(time) = self.cons.try_pop() {
+                times.push(time);
+            } else if Instant::now() > deadline {
+                self.control_screen_capture_timing(false)?;
+                return Err(CaptureTimeout(timeout).into());
+            } else {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        self.control_screen_capture_timing(false)?;
+        times.sort();
+        let trimmed = &times[1..(10 - 1)]; // Drop min & max (basic outlier trimming).
+        let total: Duration = trimmed.iter().sum();
+        let ave = total / trimmed.len() as u32;
+        Ok(Return::Delay(ave))
+    }
+}
+
+impl Drop for CaptureHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    };
+
+    use anyhow::{Result, anyhow};
+
+    use super::*;
+
+    /// Fails `failures` times, then serves 2x2 frames until switched to 3x1.
+    struct ScriptedSource {
+        failures: u32,
+        rotated: Arc<AtomicBool>,
+    }
+
+    impl FrameSource for ScriptedSource {
+        type Error = String;
+
+        fn capture(&mut self, frame: &mut Frame) -> Result<(), String> {
+            std::thread::sleep(Duration::from_millis(1));
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err("device busy".to_string());
+            }
+
+            if self.rotated.load(Ordering::Relaxed) {
+                frame.resize(3, 1);
+            } else {
+                frame.resize(2, 2);
+            }
+            frame.data.fill(7);
+            Ok(())
+        }
+    }
+
+    fn next_event(screen_cap: &ScreenCapture) -> Result<CaptureEvent> {
+        screen_cap
+            .events
+            .recv_timeout(Duration::from_secs(2))
+            .map_err(|e| anyhow!("no capture event: {}", e))
+    }
+
+    #[test]
+    fn test_capture_recovers_and_resizes() -> Result<()> {
+        let rotated = Arc::new(AtomicBool::new(false));
+        let (handle, mut screen_cap) = spawn_capture(
+            ScriptedSource {
+                failures: 2,
+                rotated: rotated.clone(),
+            },
+            2,
+            2,
+        );
+        handle.control_screen_capture(true)?;
+
+        assert_eq!(
+            next_event(&screen_cap)?,
+            CaptureEvent::Failed {
+                error: "device busy".to_string(),
+                retry_in: Backoff::INITIAL,
+            }
+        );
+        assert_eq!(
+            next_event(&screen_cap)?,
+            CaptureEvent::Failed {
+                error: "device busy".to_string(),
+                retry_in: Backoff::INITIAL * 2,
+            }
+        );
+        assert_eq!(next_event(&screen_cap)?, CaptureEvent::Recovered);
+
+        rotated.store(true, Ordering::Relaxed);
+        assert_eq!(
+            next_event(&screen_cap)?,
+            CaptureEvent::Resized {
+                width: 3,
+                height: 1
+            }
+        );
+
+        // The event is sent before the resized frame is published
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while (screen_cap.width, screen_cap.height) != (3, 1) {
+            assert!(Instant::now() < deadline, "resized frame never arrived");
+            screen_cap.update();
+        }
+        assert_eq!(screen_cap.get_screen()?.dimensions(), (3, 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_screen_shot_delay_timeout() -> Result<()> {
+        let source = ScriptedSource {
+            failures: u32::MAX,
+            rotated: Arc::new(AtomicBool::new(false)),
+        };
+        let (mut handle, _screen_cap) = spawn_capture(source, 2, 2);
+
+        let error = handle
+            .screen_shot_delay::<anyhow::Error>(Duration::from_millis(50))
+            .err()
+            .ok_or(anyhow!("timed a failing capture"))?;
+        assert!(error.downcast_ref::<CaptureTimeout>().is_some());
+
+        Ok(())
+    }
+
+    struct InstantSource;
+
+    impl FrameSource for InstantSource {
+        type Error = String;
+
+        fn capture(&mut self, _frame: &mut Frame) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fixed_frame_rate() -> Result<()> {
+        let (handle, screen_cap) = spawn_capture(InstantSource, 1, 1);
+        assert_eq!(screen_cap.stats(), CaptureStats::default());
+
+        handle.set_frame_rate(FrameRate::Fixed(50.0))?;
+        let start = Instant::now();
+        handle.control_screen_capture(true)?;
+        std::thread::sleep(Duration::from_millis(300));
+
+        // Caps rather than bands, a loaded machine only ever makes capture slower
+        let stats = screen_cap.stats();
+        let elapsed = start.elapsed().as_secs_f64();
+        assert!(stats.enabled);
+        assert_eq!(stats.target, FrameRate::Fixed(50.0));
+        assert!(stats.frames >= 5, "{stats:?}");
+        assert!(stats.frames as f64 <= elapsed * 50.0 + 1.0, "{stats:?}");
+        assert!(stats.fps > 0.0 && stats.fps <= 60.0, "{stats:?}");
+
+        handle.control_screen_capture(false)?;
+        std::thread::sleep(Duration::from_millis(50));
+        let idle = screen_cap.stats();
+        assert!(!idle.enabled);
+        assert_eq!(idle.fps, 0.0);
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(screen_cap.stats().frames, idle.frames);
+
+        Ok(())
+    }
+
+    /// Serves instantly, counting captures and noting when the capture thread let go of it.
+    struct CountingSource {
+        captures: Arc<AtomicU32>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl FrameSource for CountingSource {
+        type Error = String;
+
+        fn capture(&mut self, _frame: &mut Frame) -> Result<(), String> {
+            self.captures.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    impl Drop for CountingSource {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_blocks_until_read() -> Result<()> {
+        let captures = Arc::new(AtomicU32::new(0));
+        let dropped = Arc::new(AtomicBool::new(false));
+        let source = CountingSource {
+            captures: captures.clone(),
+            dropped: dropped.clone(),
+        };
+        let (handle, mut screen_cap) = spawn_capture(source, 1, 1);
+
+        handle.set_frame_rate(FrameRate::Adaptive { max: 1000.0 })?;
+        handle.control_screen_capture(true)?;
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(captures.load(Ordering::Relaxed), 1);
+
+        // Reads wake it right away, there is no polling delay to wait out
+        for n in 2..=4 {
+            let seen = screen_cap.stats().frames;
+            screen_cap.read();
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while screen_cap.stats().frames == seen {
+                assert!(Instant::now() < deadline, "no frame after read {n}");
+                std::thread::yield_now();
+            }
+        }
+
+        // Stops while waiting for a reader, dropping the handle waits for the source to go
+        drop(handle);
+        assert!(dropped.load(Ordering::Relaxed), "capture thread kept running");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_adaptive_frame_rate() -> Result<()> {
+        let (handle, mut screen_cap) = spawn_capture(InstantSource, 1, 1);
+
+        handle.set_frame_rate(FrameRate::Adaptive { max: 1000.0 })?;
+        handle.control_screen_capture(true)?;
+        std::thread::sleep(Duration::from_millis(50));
+
+        // Nothing was read, so only the first frame was captured
+        assert_eq!(screen_cap.stats().frames, 1);
+
+        assert!(screen_cap.update());
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while screen_cap.stats().frames < 2 {
+            assert!(Instant::now() < deadline, "no frame after reading");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        Ok(())
+    }
+
+    /// Writes a 1x2 BGRA frame bottom row first, like some emulator APIs do.
+    struct BottomUpBgra;
+
+    impl FrameSource for BottomUpBgra {
+        type Error = String;
+
+        fn capture(&mut self, frame: &mut Frame) -> Result<(), String> {
+            frame.resize(1, 2);
+            frame.data.copy_from_slice(&[0, 0, 2, 255, 0, 0, 1, 255]);
+            Ok(())
+        }
+
+        fn format(&self) -> PixelFormat {
+            PixelFormat::Bgra8
+        }
+
+        fn bottom_up(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_frames_are_normalized() -> Result<()> {
+        let (handle, mut screen_cap) = spawn_capture(BottomUpBgra, 1, 2);
+        handle.control_screen_capture(true)?;
+
+        let frame = screen_cap
+            .wait_new_frame(Duration::from_secs(2))
+            .ok_or_else(|| anyhow!("no frame"))?;
+        assert_eq!(frame.meta.format, PixelFormat::Rgba8);
+        assert_eq!(frame.data, vec![1, 0, 0, 255, 2, 0, 0, 255]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backoff_caps() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<_> = (0..12).map(|_| backoff.fail()).collect();
+
+        assert_eq!(delays[1], Duration::from_millis(20));
+        assert_eq!(delays.last(), Some(&Backoff::MAX));
+        assert!(backoff.succeed());
+        assert!(!backoff.succeed());
+        assert_eq!(backoff.fail(), Backoff::INITIAL);
+    }
+}
@@ -1,16 +1,47 @@
-use std::time::Duration;
-
-use crate::mumu::{MuMuController, MuMuError};
+use std::{
+    sync::{
+        Arc,
+        mpsc::{Receiver, TryIter},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::adb::{AdbConfig, AdbController, AdbError};
+use crate::mock::{MockConfig, MockController, MockError};
+use crate::mumu::MuMuError;
+#[cfg(windows)]
+use crate::mumu::{MuMuConfig, MuMuController};
+use crate::replay::{ReplayConfig, ReplayController, ReplayError};
+use crate::touch::{Gesture, SwipeProfile};
+use crate::{
+    BackendRegistry, BackendUri, CaptureEvent, CaptureShared, CaptureStats, ErrorClass, Frame, FrameHub,
+    FrameRate, FrameSubscriber, InputSpace, KeyAction, KeyCode, SharedFrame, SubscriberConfig,
+};
+use mtas_utils::Point;
 use thiserror::Error;
 
-use image::{ImageBuffer, Rgba};
+use image::{GrayImage, ImageBuffer, RgbImage, Rgba};
+use serde::{Deserialize, Serialize};
 use triple_buffer::Output;
 pub enum Platform {
-    MuMu,
+    #[cfg(windows)]
+    MuMu(MuMuConfig),
+    Adb(AdbConfig),
+    Mock(MockConfig),
+    /// A session recorded with [`crate::RecordingController`].
+    Replay(ReplayConfig),
+    /// A backend address like `adb://127.0.0.1:5555`, resolved with [`BackendRegistry::global`].
+    Uri(String),
 }
 
 pub enum Controller {
+    #[cfg(windows)]
     MuMu(MuMuController),
+    Adb(AdbController),
+    Mock(MockController),
+    Replay(ReplayController),
+    /// Any other backend, usually created through a [`BackendRegistry`].
+    Backend(Box<dyn ControllerBackend>),
 }
 
 #[derive(Error, Debug)]
@@ -18,17 +49,93 @@ pub enum ControllerError {
     #[error("MuMu Controller Error occurred: {0}")]
     MuMuError(#[from] MuMuError),
 
+    #[error("Adb Controller Error occurred: {0}")]
+    AdbError(#[from] AdbError),
+
+    #[error("Mock Controller Error occurred: {0}")]
+    MockError(#[from] MockError),
+
+    #[error("Replay Controller Error occurred: {0}")]
+    ReplayError(#[from] ReplayError),
+
     #[error("Image Container is Not Big Enough")]
     ScreenCaptureError(),
+
+    #[error("Controller Thread Has Stopped")]
+    Closed,
+
+    #[error("Backend Error occurred: {0}")]
+    BackendError(#[from] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("No Backend Registered For {0}://")]
+    UnknownBackend(String),
+
+    #[error("Invalid Backend Uri: {0}")]
+    InvalidUri(String),
+}
+
+impl ControllerError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            ControllerError::MuMuError(e) => e.class(),
+            ControllerError::AdbError(e) => e.class(),
+            ControllerError::MockError(e) => e.class(),
+            ControllerError::ReplayError(e) => e.class(),
+            ControllerError::Closed => ErrorClass::NeedsReconnect,
+            ControllerError::UnknownBackend(_) | ControllerError::InvalidUri(_) => {
+                ErrorClass::InvalidArgument
+            }
+            ControllerError::ScreenCaptureError() | ControllerError::BackendError(_) => {
+                ErrorClass::Fatal
+            }
+        }
+    }
+
+    /// Whether the backend lost its connection, see [`crate::Supervisor`].
+    pub fn is_disconnected(&self) -> bool {
+        self.class() == ErrorClass::NeedsReconnect
+    }
+
+    /// Whether the backend guarantees the command was not applied, so sending it again can't
+    /// run it twice. A lost connection alone does not say that, the reply may be what got lost.
+    pub fn is_unsent(&self) -> bool {
+        match self {
+            ControllerError::MuMuError(e) => e.is_unsent(),
+            ControllerError::AdbError(e) => e.is_unsent(),
+            ControllerError::MockError(e) => e.is_unsent(),
+            ControllerError::ReplayError(e) => e.is_unsent(),
+            ControllerError::Closed => true,
+            _ => false,
+        }
+    }
 }
 
 impl Platform {
-    fn new(&self) -> Result<(Controller, ScreenCapture), ControllerError> {
+    fn into_controller(self) -> Result<(Controller, ScreenCapture), ControllerError> {
         match self {
-            Platform::MuMu => {
-                let (controller, screen_capture) = MuMuController::new()?;
+            #[cfg(windows)]
+            Platform::MuMu(config) => {
+                let (controller, screen_capture) = MuMuController::new(config)?;
                 Ok((Controller::MuMu(controller), screen_capture))
             }
+            Platform::Adb(config) => {
+                let (controller, screen_capture) = AdbController::new(config)?;
+                Ok((Controller::Adb(controller), screen_capture))
+            }
+            Platform::Mock(config) => {
+                let (controller, screen_capture) = MockController::new(config)?;
+                Ok((Controller::Mock(controller), screen_capture))
+            }
+            Platform::Replay(config) => {
+                let (controller, screen_capture) = ReplayController::new(config)?;
+                Ok((Controller::Replay(controller), screen_capture))
+            }
+            Platform::Uri(uri) => {
+                let uri: BackendUri = uri.parse()?;
+                // Connecting can take long, other threads may use the registry meanwhile
+                let factory = BackendRegistry::global().read().unwrap().factory(&uri)?;
+                factory(&uri)
+            }
         }
     }
 }
@@ -36,17 +143,51 @@ impl Platform {
 impl Controller {
     pub fn execute(&mut self, command: Command) -> Result<Return, ControllerError> {
         match self {
-            Controller::MuMu(controler) => controler.execute(command).map_err(Into::into),
+            #[cfg(windows)]
+            Controller::MuMu(controler) => ControllerBackend::execute(controler, command),
+            Controller::Adb(controler) => ControllerBackend::execute(controler, command),
+            Controller::Mock(controler) => ControllerBackend::execute(controler, command),
+            Controller::Replay(controler) => ControllerBackend::execute(controler, command),
+            Controller::Backend(controler) => controler.execute(command),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Command {
     Tab {
         x: i32,
         y: i32,
     },
+    /// `Tab` at a point given in any space, out of bounds points are rejected.
+    TabAt(Point),
+    /// Press at `(x, y)` for `hold` on a finger that is not held.
+    LongPress {
+        x: i32,
+        y: i32,
+        hold: Duration,
+    },
+    LongPressAt {
+        at: Point,
+        hold: Duration,
+    },
+    /// Press `contact` and keep it down until `TouchUp`, the controller releases it
+    /// on drop or when a command fails.
+    TouchDown {
+        contact: u32,
+        x: i32,
+        y: i32,
+    },
+    /// Move a contact pressed with `TouchDown`.
+    TouchMove {
+        contact: u32,
+        x: i32,
+        y: i32,
+    },
+    TouchUp {
+        contact: u32,
+    },
+    /// Drag from `(x1, y1)` to `(x2, y2)` over `t`, following the current [`SwipeProfile`].
     Scroll {
         x1: i32,
         y1: i32,
@@ -54,52 +195,218 @@ pub enum Command {
         y2: i32,
         t: Duration,
     },
+    ScrollAt {
+        from: Point,
+        to: Point,
+        t: Duration,
+    },
+    /// How the points of `TabAt`, `LongPressAt` and `ScrollAt` map to input pixels, and the
+    /// bounds raw input pixels are checked against.
+    SetInputSpace(InputSpace),
     ControlScreenCapture {
         start: bool,
     },
     TestScreenShotDelay {},
+    /// Pace of the capture thread while capture is enabled, see [`ScreenCapture::stats`].
+    SetFrameRate(FrameRate),
+    /// Hardware or navigation key, not every backend can hold a key down.
+    Key {
+        code: KeyCode,
+        action: KeyAction,
+    },
+    /// Type UTF-8 text into the focused field.
+    Text(String),
+    /// Several fingers at once (pinch, rotate, multi-finger pans), moves are sampled
+    /// at the swipe profile rate.
+    Gesture(Gesture),
+    /// Easing and step rate used by every following `Scroll` and `Gesture`.
+    SetSwipeProfile(SwipeProfile),
+    /// Move capture and input to another display, returns [`Return::DisplayId`].
+    BindDisplay(DisplayTarget),
+}
+
+/// Which display of the device a controller is bound to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisplayTarget {
+    Id(u32),
+    /// The display an app runs on, looked up again on every bind since it changes
+    /// when the app restarts. `app_index` selects a cloned app, the main one is 0.
+    Package {
+        name: String,
+        app_index: i32,
+    },
+}
+
+impl Default for DisplayTarget {
+    fn default() -> Self {
+        DisplayTarget::Id(0)
+    }
 }
 
 pub struct ScreenCapture {
+    /// Size of the last frame read through [`ScreenCapture::update`] or [`ScreenCapture::read`].
     pub height: usize,
     pub width: usize,
-    pub capture: Output<Vec<u8>>,
+    pub capture: Output<SharedFrame>,
+    pub(crate) events: Receiver<CaptureEvent>,
+    pub(crate) shared: Arc<CaptureShared>,
+    pub(crate) hub: FrameHub,
 }
 
 impl ScreenCapture {
+    /// Fetch the newest frame, returns whether there was one since the last call.
+    pub fn update(&mut self) -> bool {
+        let updated = self.capture.update();
+        if updated {
+            // An adaptive capture waits for this
+            self.shared.wake();
+        }
+        let frame = self.capture.peek_output_buffer();
+        (self.width, self.height) = (frame.width(), frame.height());
+        updated
+    }
+
+    /// The newest frame.
+    pub fn read(&mut self) -> &Frame {
+        self.update();
+        self.capture.peek_output_buffer()
+    }
+
+    /// The newest frame as a handle that can be kept or sent to another thread without
+    /// copying, its buffer is reused once every clone is dropped.
+    pub fn latest(&mut self) -> SharedFrame {
+        self.read();
+        self.capture.peek_output_buffer().clone()
+    }
+
+    /// The newest frame as an image borrowing the capture buffer.
+    pub fn view(&mut self) -> Result<ImageBuffer<Rgba<u8>, &[u8]>, ControllerError> {
+        let frame = self.read();
+
+        ImageBuffer::from_raw(
+            frame.width() as u32,
+            frame.height() as u32,
+            frame.data.as_slice(),
+        )
+        .ok_or_else(ControllerError::ScreenCaptureError)
+    }
+
+    /// The newest frame if its `seq` is above `seq`, so a frame is never handled twice.
+    pub fn latest_if_newer(&mut self, seq: u64) -> Option<&Frame> {
+        let frame = self.read();
+        (frame.meta.seq > seq).then_some(frame)
+    }
+
+    /// Block until a frame newer than the one last read is published, `None` on timeout.
+    pub fn wait_new_frame(&mut self, timeout: Duration) -> Option<&Frame> {
+        let seen = self.capture.peek_output_buffer().meta.seq;
+        let deadline = Instant::now() + timeout;
+
+        let shared = self.shared.clone();
+        let mut stats = shared.stats.lock().unwrap();
+        while stats.frames <= seen {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            stats = shared
+                .published
+                .wait_timeout(stats, deadline - now)
+                .unwrap()
+                .0;
+        }
+        drop(stats);
+
+        Some(self.read())
+    }
+
+    /// Next pending status event of the capture thread.
+    pub fn try_event(&self) -> Option<CaptureEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Every published frame also goes here, for readers besides this one.
+    pub fn hub(&self) -> &FrameHub {
+        &self.hub
+    }
+
+    /// A reader of the live frames that can be moved to another thread.
+    pub fn subscribe(&self, config: SubscriberConfig) -> FrameSubscriber {
+        self.hub.subscribe(config)
+    }
+
+    /// Current frame rate and counters of the capture thread.
+    pub fn stats(&self) -> CaptureStats {
+        *self.shared.stats.lock().unwrap()
+    }
+
+    /// Every pending status event.
+    pub fn events(&self) -> TryIter<'_, CaptureEvent> {
+        self.events.try_iter()
+    }
+
+    /// A copy of the newest frame, top row first. [`ScreenCapture::view`] and
+    /// [`ScreenCapture::latest`] avoid the copy.
     pub fn get_screen(&mut self) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, ControllerError> {
-        let width = self.width;
-        let height = self.height;
-        let img = self.capture.read();
+        self.read()
+            .to_rgba_image()
+            .ok_or_else(ControllerError::ScreenCaptureError)
+    }
 
-        let img: ImageBuffer<Rgba<u8>, _> =
-            ImageBuffer::from_raw(width as u32, height as u32, img.clone())
-                .ok_or_else(|| ControllerError::ScreenCaptureError())?;
+    pub fn get_screen_rgb(&mut self) -> Result<RgbImage, ControllerError> {
+        self.read()
+            .to_rgb_image()
+            .ok_or_else(ControllerError::ScreenCaptureError)
+    }
 
-        Ok(img)
+    /// Grayscale like `to_luma8`, what the template matcher works on.
+    pub fn get_screen_gray(&mut self) -> Result<GrayImage, ControllerError> {
+        self.read()
+            .to_luma_image()
+            .ok_or_else(ControllerError::ScreenCaptureError)
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Return {
     Nothing,
     Delay(Duration),
+    DisplayId(u32),
 }
 
 pub trait ControllerTrait {
+    type Config;
     type Error;
 
-    fn new() -> Result<(Self, ScreenCapture), Self::Error>
+    fn new(config: Self::Config) -> Result<(Self, ScreenCapture), Self::Error>
     where
         Self: Sized;
     fn execute(&mut self, command: Command) -> Result<Return, Self::Error>;
 }
 
+/// Object-safe side of a backend, what [`Controller::Backend`] holds.
+///
+/// Every [`ControllerTrait`] whose error converts into [`ControllerError`] is one, so a
+/// backend outside this crate only needs that `From` impl.
+pub trait ControllerBackend {
+    fn execute(&mut self, command: Command) -> Result<Return, ControllerError>;
+}
+
+impl<T> ControllerBackend for T
+where
+    T: ControllerTrait,
+    T::Error: Into<ControllerError>,
+{
+    fn execute(&mut self, command: Command) -> Result<Return, ControllerError> {
+        ControllerTrait::execute(self, command).map_err(Into::into)
+    }
+}
+
 pub fn controller(pla: Platform) -> Result<(Controller, ScreenCapture), ControllerError> {
-    pla.new()
+    pla.into_controller()
 }
 
-#[cfg(test)]
+#[cfg(all(test, windows))]
 mod tests {
     use std::{
         thread::{sleep, spawn},
@@ -116,19 +423,17 @@ mod tests {
     fn test_run_loop() -> Result<()> {
         mtas_logger::init_logger!(std::io::stdout());
 
-        let (mut controller, _screen_cap) = controller(Platform::MuMu)?;
+        let (mut controller, _screen_cap) = controller(Platform::MuMu(MuMuConfig::from_env()?))?;
         info!("{:?}", controller.execute(Command::TestScreenShotDelay {}));
 
         Ok(())
     }
 
-    use image::imageops::{self, flip_vertical};
-
     #[test]
     fn test_capture_screen() -> Result<()> {
         mtas_logger::init_logger!(std::io::stdout());
 
-        let (mut controller, mut screen_cap) = controller(Platform::MuMu)?;
+        let (mut controller, mut screen_cap) = controller(Platform::MuMu(MuMuConfig::from_env()?))?;
 
         info!("{:?}", controller.execute(Command::TestScreenShotDelay {}));
 
@@ -136,12 +441,10 @@ mod tests {
 
         let img = screen_cap.get_screen()?;
 
-        let img = flip_vertical(&img);
-
         img.save("./screenshot.png")
             .map_err(|e| anyhow!("Failed to save image: {}", e))?;
 
-        let gray = imageops::grayscale(&img);
+        let gray = screen_cap.get_screen_gray()?;
 
         gray.save("./screenshot_gray.png")
             .map_err(|e| anyhow!("Failed to save image: {}", e))?;
@@ -153,7 +456,7 @@ mod tests {
     fn test_vedio_speed() -> Result<()> {
         mtas_logger::init_logger!(std::io::stdout());
 
-        let (mut controller, mut screen_cap) = controller(Platform::MuMu)?;
+        let (mut controller, mut screen_cap) = controller(Platform::MuMu(MuMuConfig::from_env()?))?;
 
         info!("{:?}", controller.execute(Command::TestScreenShotDelay {}));
 
@@ -162,7 +465,7 @@ mod tests {
         for _ in 0..10 {
             let start = Instant::now();
             'innerloop: loop {
-                if screen_cap.capture.update() {
+                if screen_cap.update() {
                     times.push(start.elapsed());
                     break 'innerloop;
                 }
@@ -183,7 +486,7 @@ mod tests {
     fn test_vedio_show() -> Result<()> {
         mtas_logger::init_logger!(std::io::stdout());
 
-        let (mut controller, mut screen_cap) = controller(Platform::MuMu)?;
+        let (mut controller, mut screen_cap) = controller(Platform::MuMu(MuMuConfig::from_env()?))?;
 
         info!("{:?}", controller.execute(Command::TestScreenShotDelay {}));
 
@@ -197,9 +500,9 @@ mod tests {
             window.set_target_fps(120);
 
             while window.is_open() && !window.is_key_down(Key::Escape) {
-                let buffer_u8 = screen_cap.capture.read();
+                let buffer_u8 = &screen_cap.read().data;
 
-                let buffer_u32_slice = bytemuck::cast_slice::<u8, u32>(&buffer_u8);
+                let buffer_u32_slice = bytemuck::cast_slice::<u8, u32>(buffer_u8);
 
                 if buffer_u32_slice.len() != width * height {
                     info!(
@@ -225,7 +528,7 @@ mod tests {
     fn test_command_sequence_performance() -> Result<()> {
         mtas_logger::init_logger!(std::io::stdout());
 
-        let (mut controller, _screen_cap) = controller(Platform::MuMu)?;
+        let (mut controller, _screen_cap) = controller(Platform::MuMu(MuMuConfig::from_env()?))?;
 
         let commands = vec![
             Command::Scroll {
@@ -0,0 +1,84 @@
+use mtas_utils::{Affine, CoordError, CoordSpace, InputPoint, Rotation, Size};
+use serde::{Deserialize, Serialize};
+
+use crate::Command;
+
+/// Input resolution and orientation of a backend, set with `Command::SetInputSpace`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputSpace {
+    /// Input pixels in the natural orientation, `None` when they match the captured frame.
+    pub size: Option<Size>,
+    pub rotation: Rotation,
+    /// Capture to input pixels as measured by [`crate::calibrate`], overrides `rotation`.
+    pub calibration: Option<Affine>,
+}
+
+impl InputSpace {
+    /// Conversions for frames of size `capture`.
+    pub fn coord_space(&self, capture: Size) -> CoordSpace {
+        let natural = if self.rotation.is_transposed() {
+            capture.transposed()
+        } else {
+            capture
+        };
+        let space = CoordSpace::new(capture, self.size.unwrap_or(natural), self.rotation);
+        match self.calibration {
+            Some(calibration) => space.with_calibration(calibration),
+            None => space,
+        }
+    }
+}
+
+impl Command {
+    /// The same command in input pixels, `TabAt`, `LongPressAt` and `ScrollAt` turn into
+    /// `Tab`, `LongPress` and `Scroll`. Other commands are returned unchanged.
+    pub fn resolve(self, space: &CoordSpace) -> Result<Command, CoordError> {
+        Ok(match self {
+            Command::TabAt(at) => {
+                let at = space.to_input(at)?;
+                Command::Tab { x: at.x, y: at.y }
+            }
+            Command::LongPressAt { at, hold } => {
+                let at = space.to_input(at)?;
+                Command::LongPress {
+                    x: at.x,
+                    y: at.y,
+                    hold,
+                }
+            }
+            Command::ScrollAt { from, to, t } => {
+                let (from, to) = (space.to_input(from)?, space.to_input(to)?);
+                Command::Scroll {
+                    x1: from.x,
+                    y1: from.y,
+                    x2: to.x,
+                    y2: to.y,
+                    t,
+                }
+            }
+            command => command,
+        })
+    }
+
+    /// Reject input pixels of `Tab`, `LongPress`, `Scroll`, `TouchDown`, `TouchMove` and
+    /// `Gesture` outside `space`, the bounds the `*At` commands are held to.
+    pub fn check(&self, space: &CoordSpace) -> Result<(), CoordError> {
+        let check = |x, y| space.to_input(InputPoint::new(x, y)).map(drop);
+        match self {
+            Command::Tab { x, y }
+            | Command::LongPress { x, y, .. }
+            | Command::TouchDown { x, y, .. }
+            | Command::TouchMove { x, y, .. } => check(*x, *y),
+            Command::Scroll { x1, y1, x2, y2, .. } => {
+                check(*x1, *y1)?;
+                check(*x2, *y2)
+            }
+            Command::Gesture(gesture) => gesture
+                .tracks
+                .iter()
+                .flat_map(|track| &track.keypoints)
+                .try_for_each(|point| check(point.x, point.y)),
+            _ => Ok(()),
+        }
+    }
+}
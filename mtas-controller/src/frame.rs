@@ -0,0 +1,412 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use image::{GrayImage, RgbImage, RgbaImage};
+
+/// Byte order of a pixel in [`Frame::data`], 4 bytes a pixel, row major.
+///
+/// Published frames are always [`PixelFormat::Rgba8`], other formats only describe what a
+/// [`crate::FrameSource`] writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelFormat {
+    #[default]
+    Rgba8,
+    Bgra8,
+}
+
+/// Where a frame came from, set by the capture thread on publish.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameMeta {
+    /// Counts published frames from 1, the blank frame before the first capture is 0.
+    pub seq: u64,
+    /// When the capture started and when it returned, the screen content is from in between.
+    pub started: Instant,
+    pub finished: Instant,
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+    /// Part of the display the frame shows, all of it unless captured for ROI leases.
+    pub region: Region,
+    /// Size of the whole display.
+    pub display: (usize, usize),
+}
+
+/// A rectangle of a frame in pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The part of `self` inside a `width` x `height` frame.
+    pub fn clamp(&self, width: usize, height: usize) -> Region {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        Region {
+            x,
+            y,
+            width: self.width.min(width - x),
+            height: self.height.min(height - y),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Smallest region covering both.
+    pub fn union(&self, other: &Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Region {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+
+    pub fn contains(&self, other: &Region) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+}
+
+/// One frame, `data` is `width * height * 4` bytes, top row first.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub meta: FrameMeta,
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Self {
+        let now = Instant::now();
+        Frame {
+            meta: FrameMeta {
+                seq: 0,
+                started: now,
+                finished: now,
+                width,
+                height,
+                format: PixelFormat::default(),
+                region: Region::new(0, 0, width, height),
+                display: (width, height),
+            },
+            data: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.meta.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.meta.height
+    }
+
+    /// How long ago the capture finished.
+    pub fn age(&self) -> Duration {
+        self.meta.finished.elapsed()
+    }
+
+    /// Whether the frame shows only part of the display.
+    pub fn is_partial(&self) -> bool {
+        self.meta.region != Region::new(0, 0, self.meta.display.0, self.meta.display.1)
+    }
+
+    /// A copy of `region` of the display, `None` unless the frame shows all of it.
+    pub fn crop(&self, region: Region) -> Option<Frame> {
+        if region.is_empty() || !self.meta.region.contains(&region) {
+            return None;
+        }
+
+        let local = Region {
+            x: region.x - self.meta.region.x,
+            y: region.y - self.meta.region.y,
+            ..region
+        };
+        Some(Frame {
+            meta: FrameMeta {
+                width: region.width,
+                height: region.height,
+                region,
+                ..self.meta
+            },
+            data: self.region_rows(local).flatten().copied().collect(),
+        })
+    }
+
+    /// RGBA bytes of every row of `region` in frame pixels, clamped to the frame.
+    pub fn region_rows(&self, region: Region) -> impl Iterator<Item = &[u8]> {
+        let region = region.clamp(self.width(), self.height());
+        let stride = self.width() * 4;
+
+        (region.y..region.y + region.height).map(move |y| {
+            let start = y * stride + region.x * 4;
+            &self.data[start..start + region.width * 4]
+        })
+    }
+
+    /// Change the size, reusing the allocation when it is large enough.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.meta.width = width;
+        self.meta.height = height;
+        self.meta.region = Region::new(0, 0, width, height);
+        self.meta.display = (width, height);
+        self.data.resize(width * height * 4, 0);
+    }
+}
+
+impl Frame {
+    /// Convert what a source wrote in `format` and `bottom_up` row order to top-down RGBA.
+    pub(crate) fn normalize(&mut self, format: PixelFormat, bottom_up: bool) {
+        if format == PixelFormat::Bgra8 {
+            for px in self.data.chunks_exact_mut(4) {
+                px.swap(0, 2);
+            }
+        }
+
+        if bottom_up {
+            flip_rows(&mut self.data, self.meta.width * 4);
+        }
+
+        self.meta.format = PixelFormat::Rgba8;
+    }
+
+    /// `None` if `data` does not hold `width * height` pixels.
+    pub fn to_rgba_image(&self) -> Option<RgbaImage> {
+        RgbaImage::from_raw(self.width() as u32, self.height() as u32, self.data.clone())
+    }
+
+    pub fn to_rgb_image(&self) -> Option<RgbImage> {
+        let rgb = self
+            .data
+            .chunks_exact(4)
+            .flat_map(|px| [px[0], px[1], px[2]])
+            .collect();
+        RgbImage::from_raw(self.width() as u32, self.height() as u32, rgb)
+    }
+
+    /// BT.709 weights like `image`'s `to_luma8`, values can differ by 1 where it rounds
+    /// through floats.
+    pub fn to_luma_image(&self) -> Option<GrayImage> {
+        let luma = self
+            .data
+            .chunks_exact(4)
+            .map(|px| {
+                ((2126 * px[0] as u32 + 7152 * px[1] as u32 + 722 * px[2] as u32 + 5000) / 10000)
+                    as u8
+            })
+            .collect();
+        GrayImage::from_raw(self.width() as u32, self.height() as u32, luma)
+    }
+}
+
+/// A published frame, cloning shares the pixels instead of copying them.
+pub type SharedFrame = Arc<PooledFrame>;
+
+/// Frame allocations recycled between the capture thread and consumers.
+#[derive(Clone, Debug, Default)]
+pub struct FramePool(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl FramePool {
+    /// Spare buffers kept around, more are freed.
+    const MAX_IDLE: usize = 4;
+
+    /// A `width` x `height` frame, reusing a returned buffer if there is one.
+    pub fn get(&self, width: usize, height: usize) -> PooledFrame {
+        let data = self.0.lock().unwrap().pop().unwrap_or_default();
+        let mut frame = Frame::new(0, 0);
+        frame.data = data;
+        frame.resize(width, height);
+
+        PooledFrame {
+            frame,
+            pool: Some(self.clone()),
+        }
+    }
+
+    /// Buffers waiting to be reused.
+    pub fn idle(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    fn put(&self, data: Vec<u8>) {
+        let mut idle = self.0.lock().unwrap();
+        if idle.len() < Self::MAX_IDLE {
+            idle.push(data);
+        }
+    }
+}
+
+/// A [`Frame`] that gives its buffer back to its [`FramePool`] when dropped.
+#[derive(Debug)]
+pub struct PooledFrame {
+    frame: Frame,
+    pool: Option<FramePool>,
+}
+
+impl PooledFrame {
+    /// A frame that is not part of any pool.
+    pub fn unpooled(frame: Frame) -> Self {
+        PooledFrame { frame, pool: None }
+    }
+}
+
+impl Deref for PooledFrame {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+impl DerefMut for PooledFrame {
+    fn deref_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+}
+
+impl Drop for PooledFrame {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.put(std::mem::take(&mut self.frame.data));
+        }
+    }
+}
+
+fn flip_rows(data: &mut [u8], stride: usize) {
+    if stride == 0 {
+        return;
+    }
+
+    let rows = data.len() / stride;
+    for i in 0..rows / 2 {
+        let (top, bottom) = data.split_at_mut((rows - 1 - i) * stride);
+        top[i * stride..(i + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba};
+
+    use super::*;
+
+    fn frame(width: usize, height: usize, data: Vec<u8>) -> Frame {
+        let mut frame = Frame::new(width, height);
+        frame.data = data;
+        frame
+    }
+
+    #[test]
+    fn test_normalize_bottom_up_bgra() {
+        #[rustfmt::skip]
+        let mut frame = frame(1, 3, vec![
+            3, 0, 30, 255,
+            2, 0, 20, 255,
+            1, 0, 10, 255,
+        ]);
+        frame.meta.format = PixelFormat::Bgra8;
+
+        frame.normalize(PixelFormat::Bgra8, true);
+
+        #[rustfmt::skip]
+        assert_eq!(frame.data, vec![
+            10, 0, 1, 255,
+            20, 0, 2, 255,
+            30, 0, 3, 255,
+        ]);
+        assert_eq!(frame.meta.format, PixelFormat::Rgba8);
+    }
+
+    #[test]
+    fn test_pool_reuses_buffers() {
+        let pool = FramePool::default();
+
+        let frame = pool.get(4, 4);
+        let ptr = frame.data.as_ptr();
+        let shared: SharedFrame = Arc::new(frame);
+        let held = shared.clone();
+
+        drop(shared);
+        assert_eq!(pool.idle(), 0);
+        drop(held);
+        assert_eq!(pool.idle(), 1);
+
+        let frame = pool.get(2, 2);
+        assert_eq!(frame.data.as_ptr(), ptr);
+        assert_eq!(frame.data.len(), 16);
+        assert_eq!(pool.idle(), 0);
+
+        let frames: Vec<_> = (0..6).map(|_| pool.get(1, 1)).collect();
+        drop(frames);
+        assert_eq!(pool.idle(), FramePool::MAX_IDLE);
+    }
+
+    #[test]
+    fn test_conversions_match_image() {
+        let img = RgbaImage::from_fn(64, 32, |x, y| {
+            Rgba([(x * 4) as u8, (y * 8) as u8, (x * y * 37 % 256) as u8, 255])
+        });
+        let frame = frame(64, 32, img.as_raw().clone());
+
+        let dynamic = DynamicImage::ImageRgba8(img.clone());
+        assert_eq!(frame.to_rgba_image(), Some(img));
+        assert_eq!(frame.to_rgb_image(), Some(dynamic.to_rgb8()));
+        let luma = frame.to_luma_image().unwrap();
+        assert!(
+            luma.as_raw()
+                .iter()
+                .zip(dynamic.to_luma8().as_raw())
+                .all(|(a, b)| a.abs_diff(*b) <= 1)
+        );
+
+        assert_eq!(
+            Frame {
+                data: vec![0; 4],
+                ..frame
+            }
+            .to_luma_image(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_crop() {
+        let mut frame = frame(3, 2, (0..24).collect());
+        frame.meta.seq = 7;
+
+        let crop = frame.crop(Region::new(1, 0, 2, 2)).unwrap();
+        assert_eq!((crop.width(), crop.height()), (2, 2));
+        assert_eq!(crop.meta.seq, 7);
+        assert_eq!(crop.meta.display, (3, 2));
+        assert!(crop.is_partial());
+        assert_eq!(crop.data[..4], [4, 5, 6, 7]);
+        assert_eq!(crop.data[8..12], [16, 17, 18, 19]);
+
+        // Crops of crops stay in display coordinates
+        let pixel = crop.crop(Region::new(2, 1, 1, 1)).unwrap();
+        assert_eq!(pixel.data, [20, 21, 22, 23]);
+        assert!(crop.crop(Region::new(0, 0, 1, 1)).is_none());
+        assert!(frame.crop(Region::new(2, 1, 2, 1)).is_none());
+    }
+}
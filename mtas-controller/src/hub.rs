@@ -0,0 +1,327 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Condvar, Mutex, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::SharedFrame;
+
+/// Fans captured frames out to any number of readers, see [`crate::ScreenCapture::subscribe`].
+///
+/// Publishing only swaps `Arc`s under short locks, a slow subscriber never holds up capture,
+/// it just skips to the newest frame.
+#[derive(Clone, Debug, Default)]
+pub struct FrameHub {
+    inner: Arc<HubInner>,
+}
+
+#[derive(Debug, Default)]
+struct HubInner {
+    latest: Mutex<Option<SharedFrame>>,
+    subscribers: Mutex<Vec<Weak<Slot>>>,
+    /// Set by [`FrameHub::close`], no frames follow.
+    closed: AtomicBool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SubscriberConfig {
+    /// Frames arriving sooner than `1 / max_fps` after the last accepted one are skipped.
+    pub max_fps: Option<f64>,
+    /// How many accepted frames to keep for [`FrameSubscriber::take_history`], 0 keeps none.
+    pub history: usize,
+}
+
+#[derive(Debug)]
+struct Slot {
+    state: Mutex<SlotState>,
+    arrived: Condvar,
+}
+
+#[derive(Debug)]
+struct SlotState {
+    config: SubscriberConfig,
+    latest: Option<SharedFrame>,
+    history: VecDeque<SharedFrame>,
+    accepted_at: Option<Instant>,
+    /// Task of a pending [`FrameSubscriber::poll_next`].
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl Slot {
+    fn notify(&self, state: &mut SlotState) {
+        self.arrived.notify_all();
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl SlotState {
+    fn offer(&mut self, frame: &SharedFrame, now: Instant) -> bool {
+        let min_interval = self
+            .config
+            .max_fps
+            .map(|fps| Duration::from_secs_f64(1.0 / fps.max(0.001)));
+
+        if let (Some(min_interval), Some(accepted_at)) = (min_interval, self.accepted_at)
+            && now - accepted_at < min_interval
+        {
+            return false;
+        }
+
+        self.accepted_at = Some(now);
+        self.latest = Some(frame.clone());
+
+        if self.config.history > 0 {
+            while self.history.len() >= self.config.history {
+                self.history.pop_front();
+            }
+            self.history.push_back(frame.clone());
+        }
+
+        true
+    }
+}
+
+/// One reader of a [`FrameHub`], unsubscribes when dropped.
+#[derive(Debug)]
+pub struct FrameSubscriber {
+    slot: Arc<Slot>,
+    /// `seq` of the last frame handed out by `try_next`/`next`.
+    seen: u64,
+}
+
+impl FrameHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hand `frame` to every subscriber whose rate limit allows it.
+    pub fn publish(&self, frame: SharedFrame) {
+        let now = Instant::now();
+
+        self.inner.subscribers.lock().unwrap().retain(|slot| {
+            let Some(slot) = slot.upgrade() else {
+                return false;
+            };
+            let mut state = slot.state.lock().unwrap();
+            if state.offer(&frame, now) {
+                slot.notify(&mut state);
+            }
+            true
+        });
+
+        *self.inner.latest.lock().unwrap() = Some(frame);
+    }
+
+    /// Tell every subscriber that no more frames will be published, done by the capture
+    /// thread when it stops.
+    pub fn close(&self) {
+        self.inner.closed.store(true, Ordering::Relaxed);
+
+        for slot in self.inner.subscribers.lock().unwrap().iter() {
+            if let Some(slot) = slot.upgrade() {
+                let mut state = slot.state.lock().unwrap();
+                state.closed = true;
+                slot.notify(&mut state);
+            }
+        }
+    }
+
+    /// Newest published frame.
+    pub fn latest(&self) -> Option<SharedFrame> {
+        self.inner.latest.lock().unwrap().clone()
+    }
+
+    /// A new reader, starting from the newest published frame.
+    pub fn subscribe(&self, config: SubscriberConfig) -> FrameSubscriber {
+        let mut state = SlotState {
+            config,
+            latest: None,
+            history: VecDeque::with_capacity(config.history),
+            accepted_at: None,
+            waker: None,
+            closed: false,
+        };
+        if let Some(frame) = self.latest() {
+            state.offer(&frame, Instant::now());
+        }
+
+        let slot = Arc::new(Slot {
+            state: Mutex::new(state),
+            arrived: Condvar::new(),
+        });
+        let mut subscribers = self.inner.subscribers.lock().unwrap();
+        slot.state.lock().unwrap().closed = self.inner.closed.load(Ordering::Relaxed);
+        subscribers.push(Arc::downgrade(&slot));
+        drop(subscribers);
+
+        FrameSubscriber { slot, seen: 0 }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.inner
+            .subscribers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|slot| slot.strong_count() > 0)
+            .count()
+    }
+}
+
+impl FrameSubscriber {
+    /// Change the rate limit, takes effect with the next published frame.
+    pub fn set_max_fps(&self, max_fps: Option<f64>) {
+        self.slot.state.lock().unwrap().config.max_fps = max_fps;
+    }
+
+    /// Newest accepted frame, whether or not it was handed out before.
+    pub fn latest(&self) -> Option<SharedFrame> {
+        self.slot.state.lock().unwrap().latest.clone()
+    }
+
+    /// Newest accepted frame if it was not handed out yet.
+    pub fn try_next(&mut self) -> Option<SharedFrame> {
+        let frame = self.latest().filter(|frame| frame.meta.seq > self.seen)?;
+        self.seen = frame.meta.seq;
+        Some(frame)
+    }
+
+    /// Block until a frame newer than the last one handed out is accepted, `None` on timeout
+    /// or once the hub is closed.
+    pub fn next(&mut self, timeout: Duration) -> Option<SharedFrame> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.slot.state.lock().unwrap();
+
+        loop {
+            if let Some(frame) = state.latest.as_ref().filter(|f| f.meta.seq > self.seen) {
+                self.seen = frame.meta.seq;
+                return Some(frame.clone());
+            }
+
+            let now = Instant::now();
+            if now >= deadline || state.closed {
+                return None;
+            }
+            state = self
+                .slot
+                .arrived
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// [`Self::try_next`] for async readers, `Ready(None)` once the hub is closed and the last
+    /// frame was handed out.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<SharedFrame>> {
+        let mut state = self.slot.state.lock().unwrap();
+
+        if let Some(frame) = state.latest.as_ref().filter(|f| f.meta.seq > self.seen) {
+            self.seen = frame.meta.seq;
+            return Poll::Ready(Some(frame.clone()));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Accepted frames kept since the last call, oldest first.
+    pub fn take_history(&self) -> Vec<SharedFrame> {
+        self.slot.state.lock().unwrap().history.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Frame, PooledFrame};
+
+    fn frame(seq: u64) -> SharedFrame {
+        let mut frame = Frame::new(1, 1);
+        frame.meta.seq = seq;
+        SharedFrame::new(PooledFrame::unpooled(frame))
+    }
+
+    fn seqs(frames: &[SharedFrame]) -> Vec<u64> {
+        frames.iter().map(|f| f.meta.seq).collect()
+    }
+
+    #[test]
+    fn test_hub_fan_out() {
+        let hub = FrameHub::new();
+        hub.publish(frame(1));
+
+        let mut live = hub.subscribe(SubscriberConfig::default());
+        let mut recorder = hub.subscribe(SubscriberConfig {
+            history: 2,
+            ..Default::default()
+        });
+
+        // New subscribers start from the current frame
+        assert_eq!(live.try_next().map(|f| f.meta.seq), Some(1));
+        assert!(live.try_next().is_none());
+
+        hub.publish(frame(2));
+        hub.publish(frame(3));
+
+        assert_eq!(live.try_next().map(|f| f.meta.seq), Some(3));
+        assert_eq!(recorder.try_next().map(|f| f.meta.seq), Some(3));
+        assert_eq!(seqs(&recorder.take_history()), vec![2, 3]);
+        assert!(recorder.take_history().is_empty());
+
+        drop(recorder);
+        assert_eq!(hub.subscriber_count(), 1);
+        hub.publish(frame(4));
+        assert_eq!(hub.inner.subscribers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_hub_rate_limit() {
+        let hub = FrameHub::new();
+        let limited = hub.subscribe(SubscriberConfig {
+            max_fps: Some(10.0),
+            history: 10,
+        });
+
+        hub.publish(frame(1));
+        hub.publish(frame(2));
+        std::thread::sleep(Duration::from_millis(120));
+        hub.publish(frame(3));
+
+        assert_eq!(seqs(&limited.take_history()), vec![1, 3]);
+
+        limited.set_max_fps(None);
+        hub.publish(frame(4));
+        assert_eq!(seqs(&limited.take_history()), vec![4]);
+    }
+
+    #[test]
+    fn test_hub_blocking_next() {
+        let hub = FrameHub::new();
+        let mut sub = hub.subscribe(SubscriberConfig::default());
+
+        assert!(sub.next(Duration::from_millis(10)).is_none());
+
+        let publisher = hub.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            publisher.publish(frame(7));
+        });
+
+        assert_eq!(
+            sub.next(Duration::from_secs(2)).map(|f| f.meta.seq),
+            Some(7)
+        );
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::*;
+
+use crate::{
+    Command, Controller, ControllerBackend, ControllerError, ErrorClass, Frame, FrameHub, Return,
+    ScreenCapture,
+};
+
+/// One command of a recorded session, a line of [`Journal::SESSION`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Time since recording started.
+    pub at: Duration,
+    /// `seq` of the newest frame when the command was sent, 0 before the first one.
+    pub frame: u64,
+    pub command: Command,
+    /// What the controller returned, errors as their message.
+    pub result: Result<Return, String>,
+    /// Class of the error in `result`, so a replay retries where the recording did.
+    /// Missing in sessions recorded before it was kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_class: Option<ErrorClass>,
+}
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("Journal Io Failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid Journal Entry: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Failed to Save Journal Frame: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Image Container is Not Big Enough")]
+    Frame,
+}
+
+/// A session directory: every command in [`Journal::SESSION`] and the frames they were
+/// sent on as `frames/<seq>.png`.
+pub struct Journal {
+    dir: PathBuf,
+    session: BufWriter<File>,
+    started: Instant,
+    saved: BTreeSet<u64>,
+}
+
+impl Journal {
+    pub const SESSION: &'static str = "session.jsonl";
+    pub const FRAMES: &'static str = "frames";
+
+    /// Start a session in `dir`, replacing any session recorded there before.
+    pub fn create(dir: impl Into<PathBuf>) -> Result<Self, JournalError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(dir.join(Self::FRAMES))?;
+        let session = BufWriter::new(File::create(dir.join(Self::SESSION))?);
+
+        Ok(Journal {
+            dir,
+            session,
+            started: Instant::now(),
+            saved: BTreeSet::new(),
+        })
+    }
+
+    pub fn frame_path(dir: &Path, seq: u64) -> PathBuf {
+        dir.join(Self::FRAMES).join(format!("{seq:08}.png"))
+    }
+
+    /// Append `command` and its result, saving `frame` unless it was saved before.
+    pub fn record(
+        &mut self,
+        command: &Command,
+        result: &Result<Return, ControllerError>,
+        frame: Option<&Frame>,
+    ) -> Result<(), JournalError> {
+        let seq = frame.map_or(0, |frame| frame.meta.seq);
+        if let Some(frame) = frame
+            && seq > 0
+            && self.saved.insert(seq)
+        {
+            frame
+                .to_rgba_image()
+                .ok_or(JournalError::Frame)?
+                .save(Self::frame_path(&self.dir, seq))?;
+        }
+
+        let entry = JournalEntry {
+            at: self.started.elapsed(),
+            frame: seq,
+            command: command.clone(),
+            result: result.as_ref().cloned().map_err(|e| e.to_string()),
+            error_class: result.as_ref().err().map(ControllerError::class),
+        };
+        serde_json::to_writer(&mut self.session, &entry)?;
+        // One line at a time, so a crash keeps everything before it
+        self.session.write_all(b"\n")?;
+        self.session.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Every entry of the session in `dir`.
+pub fn read_journal(dir: impl AsRef<Path>) -> Result<Vec<JournalEntry>, JournalError> {
+    let file = File::open(dir.as_ref().join(Journal::SESSION))?;
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(entries)
+}
+
+/// A controller that writes every command it executes to a [`Journal`], replay it with
+/// `Platform::Replay`.
+pub struct RecordingController {
+    inner: Controller,
+    journal: Journal,
+    hub: FrameHub,
+}
+
+impl RecordingController {
+    pub fn new(inner: Controller, screen_cap: &ScreenCapture, journal: Journal) -> Self {
+        RecordingController {
+            inner,
+            journal,
+            hub: screen_cap.hub().clone(),
+        }
+    }
+
+    pub fn into_inner(self) -> Controller {
+        self.inner
+    }
+}
+
+impl ControllerBackend for RecordingController {
+    fn execute(&mut self, command: Command) -> Result<Return, ControllerError> {
+        let frame = self.hub.latest();
+        let result = self.inner.execute(command.clone());
+
+        if let Err(e) = self
+            .journal
+            .record(&command, &result, frame.as_deref().map(|f| &**f))
+        {
+            warn!("Failed to journal {:?}: {}", command, e);
+        }
+
+        result
+    }
+}
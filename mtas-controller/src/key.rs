@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+/// Hardware and navigation keys, see Android's `KeyEvent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyCode {
+    Home,
+    Back,
+    AppSwitch,
+    Menu,
+    Enter,
+    /// Backspace.
+    Delete,
+    Tab,
+    Space,
+    Escape,
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight,
+    VolumeUp,
+    VolumeDown,
+    VolumeMute,
+    Power,
+    /// Any other Android keycode.
+    Other(i32),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyAction {
+    /// Down and up.
+    #[default]
+    Press,
+    Down,
+    Up,
+}
+
+/// (key, Android keycode, linux input event code)
+const KEY_TABLE: &[(KeyCode, i32, i32)] = &[
+    (KeyCode::Home, 3, 172),
+    (KeyCode::Back, 4, 158),
+    (KeyCode::AppSwitch, 187, 580),
+    (KeyCode::Menu, 82, 139),
+    (KeyCode::Enter, 66, 28),
+    (KeyCode::Delete, 67, 14),
+    (KeyCode::Tab, 61, 15),
+    (KeyCode::Space, 62, 57),
+    (KeyCode::Escape, 111, 1),
+    (KeyCode::DpadUp, 19, 103),
+    (KeyCode::DpadDown, 20, 108),
+    (KeyCode::DpadLeft, 21, 105),
+    (KeyCode::DpadRight, 22, 106),
+    (KeyCode::VolumeUp, 24, 115),
+    (KeyCode::VolumeDown, 25, 114),
+    (KeyCode::VolumeMute, 164, 113),
+    (KeyCode::Power, 26, 116),
+];
+
+impl KeyCode {
+    /// Named key for an Android keycode, `Other` if it has no name.
+    pub fn from_android(code: i32) -> Self {
+        KEY_TABLE
+            .iter()
+            .find(|(_, android, _)| *android == code)
+            .map_or(KeyCode::Other(code), |(key, _, _)| *key)
+    }
+
+    /// Keycode as used by `input keyevent`.
+    pub fn android(&self) -> i32 {
+        match self {
+            KeyCode::Other(code) => *code,
+            key => KEY_TABLE
+                .iter()
+                .find(|(k, _, _)| k == key)
+                .map(|(_, android, _)| *android)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Linux input event code (`input-event-codes.h`), what MuMu's key events take.
+    pub fn linux(&self) -> Option<i32> {
+        let key = match self {
+            KeyCode::Other(code) => KeyCode::from_android(*code),
+            key => *key,
+        };
+        KEY_TABLE
+            .iter()
+            .find(|(k, _, _)| *k == key)
+            .map(|(_, _, linux)| *linux)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_codes() {
+        assert_eq!(KeyCode::Back.android(), 4);
+        assert_eq!(KeyCode::Back.linux(), Some(158));
+        assert_eq!(KeyCode::AppSwitch.linux(), Some(580));
+        assert_eq!(KeyCode::from_android(66), KeyCode::Enter);
+        assert_eq!(KeyCode::Other(66).linux(), Some(28));
+        assert_eq!(KeyCode::Other(300).android(), 300);
+        assert_eq!(KeyCode::Other(300).linux(), None);
+
+        for (key, android, _) in KEY_TABLE {
+            assert_eq!(KeyCode::from_android(*android), *key);
+        }
+    }
+}
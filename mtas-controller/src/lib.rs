@@ -1,2 +1,4 @@
-mtas_macro::mod_pub!(mumu);
-mtas_macro::mod_flat!(controller);
+mtas_macro::mod_pub!(adb, minitouch, mock, mumu, replay, touch);
+mtas_macro::mod_flat!(controller, capture, frame, hub, key);
+mtas_macro::mod_flat!(async_controller, bench, calibration, coords, journal);
+mtas_macro::mod_flat!(recorder, registry, retry, roi, supervisor);
@@ -0,0 +1,324 @@
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Read, Write},
+    time::Duration,
+};
+
+use crate::touch::TouchSink;
+use thiserror::Error;
+
+use tracing::*;
+
+/// Limits announced by the minitouch (or maatouch) server on connect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MinitouchInfo {
+    pub version: u32,
+    pub max_contacts: u32,
+    pub max_x: u32,
+    pub max_y: u32,
+    pub max_pressure: u32,
+    pub pid: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum MinitouchError {
+    #[error("Minitouch Socket Error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Minitouch Handshake Failed: {0}")]
+    Handshake(String),
+
+    #[error("Minitouch Contact {contact} Out Of Range (max {max})")]
+    ContactOutOfRange { contact: u32, max: u32 },
+}
+
+/// Input transport speaking the minitouch text protocol.
+///
+/// Commands are batched in memory and only written to the socket on [`commit`],
+/// so every contact change of one commit reaches the device in a single write.
+///
+/// [`commit`]: MinitouchTransport::commit
+pub struct MinitouchTransport<S: Read + Write> {
+    stream: S,
+    info: MinitouchInfo,
+    /// Size of the coordinate space callers use, scaled to `max_x`/`max_y` when set.
+    screen: Option<(u32, u32)>,
+    pressure: u32,
+    pending: String,
+}
+
+impl<S: Read + Write> MinitouchTransport<S> {
+    pub fn new(mut stream: S) -> Result<Self, MinitouchError> {
+        let info = read_header(&mut stream)?;
+
+        info!("Minitouch connected: {:?}", info);
+
+        Ok(MinitouchTransport {
+            stream,
+            info,
+            screen: None,
+            pressure: info.max_pressure.clamp(1, 50),
+            pending: String::new(),
+        })
+    }
+
+    pub fn info(&self) -> &MinitouchInfo {
+        &self.info
+    }
+
+    /// Map coordinates from a `width`x`height` screen instead of the raw touch device range.
+    pub fn set_screen_size(&mut self, width: u32, height: u32) {
+        self.screen = Some((width, height));
+    }
+
+    pub fn set_pressure(&mut self, pressure: u32) {
+        self.pressure = pressure.min(self.info.max_pressure);
+    }
+
+    pub fn down(&mut self, contact: u32, x: i32, y: i32) -> Result<&mut Self, MinitouchError> {
+        self.check_contact(contact)?;
+        let (x, y) = self.scale(x, y);
+        let _ = writeln!(self.pending, "d {contact} {x} {y} {}", self.pressure);
+        Ok(self)
+    }
+
+    pub fn move_to(&mut self, contact: u32, x: i32, y: i32) -> Result<&mut Self, MinitouchError> {
+        self.check_contact(contact)?;
+        let (x, y) = self.scale(x, y);
+        let _ = writeln!(self.pending, "m {contact} {x} {y} {}", self.pressure);
+        Ok(self)
+    }
+
+    pub fn up(&mut self, contact: u32) -> Result<&mut Self, MinitouchError> {
+        self.check_contact(contact)?;
+        let _ = writeln!(self.pending, "u {contact}");
+        Ok(self)
+    }
+
+    /// Queue a server side delay, it only takes effect after the next commit.
+    pub fn wait(&mut self, t: Duration) -> &mut Self {
+        let _ = writeln!(self.pending, "w {}", t.as_millis());
+        self
+    }
+
+    /// Release every contact.
+    pub fn reset(&mut self) -> &mut Self {
+        self.pending.push_str("r\n");
+        self
+    }
+
+    /// Append `c` and send the whole batch.
+    pub fn commit(&mut self) -> Result<(), MinitouchError> {
+        self.pending.push_str("c\n");
+        self.flush()
+    }
+
+    /// Send queued commands without committing them.
+    pub fn flush(&mut self) -> Result<(), MinitouchError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        trace!("minitouch <- {:?}", self.pending);
+
+        let result = self
+            .stream
+            .write_all(self.pending.as_bytes())
+            .and_then(|_| self.stream.flush());
+        self.pending.clear();
+
+        Ok(result?)
+    }
+
+    fn check_contact(&self, contact: u32) -> Result<(), MinitouchError> {
+        if contact >= self.info.max_contacts {
+            return Err(MinitouchError::ContactOutOfRange {
+                contact,
+                max: self.info.max_contacts,
+            });
+        }
+        Ok(())
+    }
+
+    fn scale(&self, x: i32, y: i32) -> (u32, u32) {
+        let (x, y) = (x.max(0) as u64, y.max(0) as u64);
+        let (x, y) = match self.screen {
+            Some((width, height)) if width > 0 && height > 0 => (
+                x * self.info.max_x as u64 / width as u64,
+                y * self.info.max_y as u64 / height as u64,
+            ),
+            _ => (x, y),
+        };
+        (
+            x.min(self.info.max_x as u64) as u32,
+            y.min(self.info.max_y as u64) as u32,
+        )
+    }
+}
+
+/// Every call is committed on its own so it takes effect immediately.
+impl<S: Read + Write> TouchSink for MinitouchTransport<S> {
+    type Error = MinitouchError;
+
+    fn touch_down(&mut self, contact: u32, x: i32, y: i32) -> Result<(), MinitouchError> {
+        self.down(contact, x, y)?.commit()
+    }
+
+    fn touch_move(&mut self, contact: u32, x: i32, y: i32) -> Result<(), MinitouchError> {
+        self.move_to(contact, x, y)?.commit()
+    }
+
+    fn touch_up(&mut self, contact: u32) -> Result<(), MinitouchError> {
+        self.up(contact)?.commit()
+    }
+}
+
+/// Parse the `v <version>`, `^ <contacts> <x> <y> <pressure>` and `$ <pid>` lines.
+fn read_header<S: Read>(stream: &mut S) -> Result<MinitouchInfo, MinitouchError> {
+    let mut info = MinitouchInfo {
+        version: 0,
+        max_contacts: 0,
+        max_x: 0,
+        max_y: 0,
+        max_pressure: 0,
+        pid: 0,
+    };
+
+    // Read byte by byte, the socket must stay usable for commands afterwards
+    let mut reader = BufReader::with_capacity(1, stream);
+    let mut seen_limits = false;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(MinitouchError::Handshake("connection closed".to_string()));
+        }
+
+        let mut fields = line.split_whitespace();
+        let kind = fields.next();
+        let values = fields
+            .map(|f| {
+                f.parse::<u32>()
+                    .map_err(|_| MinitouchError::Handshake(format!("bad header line {line:?}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match (kind, values.as_slice()) {
+            (Some("v"), [version]) => info.version = *version,
+            (Some("^"), [contacts, x, y, pressure]) => {
+                info.max_contacts = *contacts;
+                info.max_x = *x;
+                info.max_y = *y;
+                info.max_pressure = *pressure;
+                seen_limits = true;
+            }
+            (Some("$"), [pid]) => {
+                info.pid = *pid;
+                break;
+            }
+            _ => {
+                return Err(MinitouchError::Handshake(format!(
+                    "bad header line {line:?}"
+                )));
+            }
+        }
+    }
+
+    if !seen_limits {
+        return Err(MinitouchError::Handshake("missing limits".to_string()));
+    }
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        net::{TcpListener, TcpStream},
+        thread::spawn,
+    };
+
+    use anyhow::Result;
+
+    use super::*;
+
+    /// Local stand-in for the minitouch socket, returns everything the client wrote.
+    fn stand_in(header: &'static str) -> Result<(TcpStream, std::thread::JoinHandle<String>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+
+        let server = spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(header.as_bytes()).unwrap();
+            let mut received = String::new();
+            stream.read_to_string(&mut received).unwrap();
+            received
+        });
+
+        Ok((TcpStream::connect(addr)?, server))
+    }
+
+    #[test]
+    fn test_minitouch_command_stream() -> Result<()> {
+        let (stream, server) = stand_in("v 1\n^ 10 1079 1919 2048\n$ 4242\n")?;
+
+        let mut touch = MinitouchTransport::new(stream)?;
+        assert_eq!(
+            *touch.info(),
+            MinitouchInfo {
+                version: 1,
+                max_contacts: 10,
+                max_x: 1079,
+                max_y: 1919,
+                max_pressure: 2048,
+                pid: 4242,
+            }
+        );
+
+        touch.down(0, 10, 20)?.down(1, 30, 40)?.commit()?;
+        touch.move_to(1, 35, 45)?.wait(Duration::from_millis(5));
+        touch.commit()?;
+        touch.up(0)?.up(1)?.commit()?;
+        drop(touch);
+
+        assert_eq!(
+            server.join().unwrap(),
+            "d 0 10 20 50\nd 1 30 40 50\nc\nm 1 35 45 50\nw 5\nc\nu 0\nu 1\nc\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minitouch_scales_and_clamps() -> Result<()> {
+        let (stream, server) = stand_in("v 1\n^ 2 999 1999 255\n$ 1\n")?;
+
+        let mut touch = MinitouchTransport::new(stream)?;
+        touch.set_screen_size(500, 1000);
+        touch.set_pressure(100);
+
+        touch.down(0, 250, 500)?.move_to(0, 900, -5)?.commit()?;
+        assert!(matches!(
+            touch.down(2, 0, 0),
+            Err(MinitouchError::ContactOutOfRange { contact: 2, max: 2 })
+        ));
+        drop(touch);
+
+        assert_eq!(
+            server.join().unwrap(),
+            "d 0 499 999 100\nm 0 999 0 100\nc\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minitouch_bad_header() {
+        let header: &[u8] = b"v 1\n$ 1\n";
+        assert!(matches!(
+            read_header(&mut &header[..]),
+            Err(MinitouchError::Handshake(_))
+        ));
+    }
+}
@@ -0,0 +1,1144 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        mpsc::SendError,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    CaptureHandle, CaptureTimeout, Command, ControllerTrait, DisplayTarget, ErrorClass, Frame,
+    FrameSource, InputSpace, Region, Return, ScreenCapCommand, ScreenCapture, spawn_capture,
+    touch::{
+        Gesture, GestureError, HoldError, HoldTouch, SwipeProfile, TouchEvent, TouchSink,
+        long_press, run_path, run_timeline,
+    },
+};
+use image::RgbaImage;
+use mtas_utils::{Affine, CoordError};
+use thiserror::Error;
+
+use tracing::*;
+
+/// Where the mock backend takes its frames from.
+#[derive(Clone, Debug)]
+pub enum MockFrames {
+    /// Every `*.png` in the directory, served in file name order.
+    Dir(PathBuf),
+    Images(Vec<RgbaImage>),
+}
+
+#[derive(Clone, Debug)]
+pub struct MockConfig {
+    pub frames: MockFrames,
+    /// Simulated time one capture takes.
+    pub frame_interval: Duration,
+    /// Simulated time every command takes before it is carried out.
+    pub command_delay: Duration,
+    /// Every command received by the controller is appended here.
+    pub log: CommandLog,
+    /// Draw a marker on the frames wherever a finger is down and for [`MARKER_FADE`] after it
+    /// is lifted, placed by this transform from input to capture pixels. Stands in for
+    /// Android's show taps option.
+    pub touch_feedback: Option<Affine>,
+    /// Drops and refuses connections on demand.
+    pub link: MockLink,
+}
+
+impl MockConfig {
+    pub fn from_dir(dir: impl Into<PathBuf>) -> Self {
+        Self::with_frames(MockFrames::Dir(dir.into()))
+    }
+
+    pub fn from_images(images: Vec<RgbaImage>) -> Self {
+        Self::with_frames(MockFrames::Images(images))
+    }
+
+    fn with_frames(frames: MockFrames) -> Self {
+        MockConfig {
+            frames,
+            frame_interval: Duration::from_millis(16),
+            command_delay: Duration::ZERO,
+            log: CommandLog::default(),
+            touch_feedback: None,
+            link: MockLink::default(),
+        }
+    }
+}
+
+/// Stands in for the emulator going away: shared by every controller made from a config,
+/// it can drop their connections and refuse new ones.
+#[derive(Clone, Debug, Default)]
+pub struct MockLink(Arc<LinkState>);
+
+#[derive(Debug, Default)]
+struct LinkState {
+    /// Bumped by every drop, connections made before it are dead.
+    generation: AtomicU64,
+    refusals: AtomicU32,
+    busy: AtomicU32,
+    lost_replies: AtomicU32,
+}
+
+impl MockLink {
+    /// Every connection made so far fails from now on.
+    pub fn drop_connections(&self) {
+        self.0.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Fail the next `attempts` connects.
+    pub fn refuse(&self, attempts: u32) {
+        self.0.refusals.store(attempts, Ordering::Relaxed);
+    }
+
+    /// Fail the next `commands` commands with [`MockError::Busy`].
+    pub fn busy(&self, commands: u32) {
+        self.0.busy.store(commands, Ordering::Relaxed);
+    }
+
+    /// Run the next `commands` commands, then drop the connection before they answer.
+    pub fn lose_replies(&self, commands: u32) {
+        self.0.lost_replies.store(commands, Ordering::Relaxed);
+    }
+
+    fn connect(&self) -> Result<u64, MockError> {
+        let refused = self
+            .0
+            .refusals
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok();
+        if refused {
+            return Err(MockError::Disconnected);
+        }
+        Ok(self.0.generation.load(Ordering::Relaxed))
+    }
+
+    fn check(&self, generation: u64) -> Result<(), MockError> {
+        if self.0.generation.load(Ordering::Relaxed) != generation {
+            return Err(MockError::Disconnected);
+        }
+        Ok(())
+    }
+
+    fn check_busy(&self) -> Result<(), MockError> {
+        let busy = self
+            .0
+            .busy
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok();
+        if busy {
+            return Err(MockError::Busy);
+        }
+        Ok(())
+    }
+
+    fn check_reply(&self) -> Result<(), MockError> {
+        let lost = self
+            .0
+            .lost_replies
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok();
+        if lost {
+            self.drop_connections();
+            return Err(MockError::ReplyLost);
+        }
+        Ok(())
+    }
+}
+
+/// Shared record of the commands a [`MockController`] has executed and the touch
+/// events they turned into.
+#[derive(Clone, Debug, Default)]
+pub struct CommandLog(Arc<Mutex<MockLog>>);
+
+#[derive(Debug, Default)]
+struct MockLog {
+    commands: Vec<Command>,
+    touches: Vec<TouchEvent>,
+    region_captures: u64,
+}
+
+impl CommandLog {
+    pub fn push(&self, command: Command) {
+        self.0.lock().unwrap().commands.push(command);
+    }
+
+    pub fn push_touch(&self, event: TouchEvent) {
+        self.0.lock().unwrap().touches.push(event);
+    }
+
+    pub fn commands(&self) -> Vec<Command> {
+        self.0.lock().unwrap().commands.clone()
+    }
+
+    pub fn touches(&self) -> Vec<TouchEvent> {
+        self.0.lock().unwrap().touches.clone()
+    }
+
+    /// Captures that grabbed only a region of interest, see [`crate::RoiLease`].
+    pub fn region_captures(&self) -> u64 {
+        self.0.lock().unwrap().region_captures
+    }
+
+    pub fn clear(&self) {
+        let mut log = self.0.lock().unwrap();
+        log.commands.clear();
+        log.touches.clear();
+        log.region_captures = 0;
+    }
+}
+
+pub struct MockController {
+    capture: CaptureHandle,
+    log: CommandLog,
+    swipe: SwipeProfile,
+    held: BTreeSet<u32>,
+    input: InputSpace,
+    /// Fingers down or lifted lately, drawn by [`MockFrameSource`] with `touch_feedback`.
+    touches: Touches,
+    link: MockLink,
+    generation: u64,
+    command_delay: Duration,
+}
+
+/// Position of every contact and when it was lifted, `None` while it is down.
+type Touches = Arc<Mutex<BTreeMap<u32, ((i32, i32), Option<Instant>)>>>;
+
+/// How long the marker of a lifted finger stays on the frames.
+pub const MARKER_FADE: Duration = Duration::from_millis(100);
+
+#[derive(Error, Debug)]
+pub enum MockError {
+    #[error("Failed to read mock frames: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to decode mock frame: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("No mock frames to serve")]
+    NoFrames,
+
+    #[error("Mock frame {index} is {got:?}, expected {expected:?}")]
+    FrameSizeMismatch {
+        index: usize,
+        expected: (u32, u32),
+        got: (u32, u32),
+    },
+
+    #[error("Invalid Gesture: {0}")]
+    Gesture(#[from] GestureError),
+
+    #[error("Invalid Touch: {0}")]
+    Hold(#[from] HoldError),
+
+    #[error("Invalid Point: {0}")]
+    Coord(#[from] CoordError),
+
+    #[error("Screen Capture Command Failed to Send：{0}")]
+    ScreenCap(#[from] SendError<ScreenCapCommand>),
+
+    #[error("Screen Capture Timing Failed: {0}")]
+    CaptureTimeout(#[from] CaptureTimeout),
+
+    #[error("Mock Connection Dropped")]
+    Disconnected,
+
+    #[error("Mock Connection Dropped After The Command Ran")]
+    ReplyLost,
+
+    #[error("Mock Backend Busy")]
+    Busy,
+}
+
+impl MockError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            MockError::Io(_)
+            | MockError::Image(_)
+            | MockError::NoFrames
+            | MockError::FrameSizeMismatch { .. } => ErrorClass::Fatal,
+            MockError::Gesture(_) | MockError::Hold(_) | MockError::Coord(_) => {
+                ErrorClass::InvalidArgument
+            }
+            MockError::Busy => ErrorClass::Transient,
+            MockError::ScreenCap(_)
+            | MockError::CaptureTimeout(_)
+            | MockError::Disconnected
+            | MockError::ReplyLost => ErrorClass::NeedsReconnect,
+        }
+    }
+
+    /// Whether the command never reached the backend, see
+    /// [`crate::ControllerError::is_unsent`].
+    pub fn is_unsent(&self) -> bool {
+        matches!(self, MockError::ScreenCap(_) | MockError::Disconnected)
+    }
+}
+
+struct MockFrameSource {
+    frames: Vec<RgbaImage>,
+    index: usize,
+    frame_interval: Duration,
+    touch_feedback: Option<Affine>,
+    touches: Touches,
+    link: MockLink,
+    generation: u64,
+    log: CommandLog,
+}
+
+impl MockFrameSource {
+    /// A 5x5 square at every finger, black on light pixels and white on dark ones.
+    fn draw_touches(&self, frame: &mut Frame, feedback: &Affine, region: Region) {
+        let (width, height) = (frame.width() as i64, frame.height() as i64);
+
+        let touches = self.touches.lock().unwrap();
+        let shown = touches
+            .values()
+            .filter(|(_, lifted)| lifted.is_none_or(|lifted| lifted.elapsed() < MARKER_FADE));
+        for &((x, y), _) in shown {
+            let (cx, cy) = feedback.apply(x as f64, y as f64);
+            let (cx, cy) = (
+                cx.round() as i64 - region.x as i64,
+                cy.round() as i64 - region.y as i64,
+            );
+
+            for py in (cy - 2).max(0)..=(cy + 2).min(height - 1) {
+                for px in (cx - 2).max(0)..=(cx + 2).min(width - 1) {
+                    let i = ((py * width + px) * 4) as usize;
+                    let pixel = &mut frame.data[i..i + 3];
+                    let value = if pixel[1] < 128 { 255 } else { 0 };
+                    pixel.fill(value);
+                }
+            }
+        }
+    }
+}
+
+impl FrameSource for MockFrameSource {
+    type Error = MockError;
+
+    fn capture(&mut self, frame: &mut Frame) -> Result<(), MockError> {
+        let image = &self.frames[self.index];
+        let full = Region::new(0, 0, image.width() as usize, image.height() as usize);
+        self.serve(frame, full)
+    }
+
+    fn capture_region(&mut self, frame: &mut Frame, region: Region) -> Result<bool, MockError> {
+        self.serve(frame, region)?;
+        self.log.0.lock().unwrap().region_captures += 1;
+        Ok(true)
+    }
+}
+
+impl MockFrameSource {
+    /// Copy `region` of the next image, taking `frame_interval` scaled by its share of the
+    /// pixels.
+    fn serve(&mut self, frame: &mut Frame, region: Region) -> Result<(), MockError> {
+        let image = &self.frames[self.index];
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let region = region.clamp(width, height);
+
+        let share = (region.width * region.height) as f64 / (width * height).max(1) as f64;
+        sleep(self.frame_interval.mul_f64(share));
+        self.link.check(self.generation)?;
+
+        frame.resize(region.width, region.height);
+        let row_len = region.width * 4;
+        for (y, row) in frame.data.chunks_exact_mut(row_len).enumerate() {
+            let start = ((region.y + y) * width + region.x) * 4;
+            row.copy_from_slice(&image.as_raw()[start..start + row_len]);
+        }
+        self.index = (self.index + 1) % self.frames.len();
+
+        if let Some(feedback) = &self.touch_feedback {
+            self.draw_touches(frame, feedback, region);
+        }
+
+        Ok(())
+    }
+}
+
+fn load_frames(frames: MockFrames) -> Result<Vec<RgbaImage>, MockError> {
+    match frames {
+        MockFrames::Images(images) => Ok(images),
+        MockFrames::Dir(dir) => {
+            let mut paths = std::fs::read_dir(&dir)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            paths.retain(|p| {
+                p.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+            });
+            paths.sort();
+
+            paths
+                .iter()
+                .map(|p| Ok(image::open(p)?.to_rgba8()))
+                .collect()
+        }
+    }
+}
+
+impl ControllerTrait for MockController {
+    type Config = MockConfig;
+    type Error = MockError;
+
+    fn new(config: MockConfig) -> Result<(Self, ScreenCapture), MockError> {
+        let generation = config.link.connect()?;
+        let images = load_frames(config.frames)?;
+
+        let first = images.first().ok_or(MockError::NoFrames)?;
+        let expected = first.dimensions();
+
+        if let Some((index, img)) = images
+            .iter()
+            .enumerate()
+            .find(|(_, img)| img.dimensions() != expected)
+        {
+            return Err(MockError::FrameSizeMismatch {
+                index,
+                expected,
+                got: img.dimensions(),
+            });
+        }
+
+        info!(
+            "Mock controller serving {} frames of {:?}",
+            images.len(),
+            expected
+        );
+
+        let touches = Touches::default();
+        let (capture, screen_capture) = spawn_capture(
+            MockFrameSource {
+                frames: images,
+                index: 0,
+                frame_interval: config.frame_interval,
+                touch_feedback: config.touch_feedback,
+                touches: touches.clone(),
+                link: config.link.clone(),
+                generation,
+                log: config.log.clone(),
+            },
+            expected.0 as usize,
+            expected.1 as usize,
+        );
+
+        Ok((
+            MockController {
+                capture,
+                log: config.log,
+                swipe: SwipeProfile::default(),
+                held: BTreeSet::new(),
+                input: InputSpace::default(),
+                touches,
+                link: config.link,
+                generation,
+                command_delay: config.command_delay,
+            },
+            screen_capture,
+        ))
+    }
+
+    #[instrument(skip_all)]
+    fn execute(&mut self, command: Command) -> Result<Return, MockError> {
+        self.log.push(command.clone());
+        self.link.check(self.generation)?;
+        self.link.check_busy()?;
+        sleep(self.command_delay);
+
+        let result = self
+            .dispatch(command)
+            .and_then(|ret| self.link.check_reply().map(|()| ret));
+
+        if result.is_err() {
+            let _ = self.release_held();
+        }
+        result
+    }
+}
+
+impl MockController {
+    fn dispatch(&mut self, command: Command) -> Result<Return, MockError> {
+        let space = self.input.coord_space(self.capture.frame_size());
+        command.check(&space)?;
+
+        match command {
+            Command::Tab { x, y } => {
+                let contact = self.free_contact();
+                self.touch_down(contact, x, y)?;
+                self.touch_up(contact)?;
+                Ok(Return::Nothing)
+            }
+            Command::Scroll { x1, y1, x2, y2, t } => {
+                let contact = self.free_contact();
+                run_path(self, contact, &self.swipe.path(x1, y1, x2, y2, t))?;
+                Ok(Return::Nothing)
+            }
+            Command::LongPress { x, y, hold } => {
+                let contact = self.free_contact();
+                long_press(self, contact, x, y, hold)?;
+                Ok(Return::Nothing)
+            }
+            Command::TouchDown { contact, x, y } => {
+                self.hold_down(contact, x, y)?;
+                Ok(Return::Nothing)
+            }
+            Command::TouchMove { contact, x, y } => {
+                self.hold_move(contact, x, y)?;
+                Ok(Return::Nothing)
+            }
+            Command::TouchUp { contact } => {
+                self.hold_up(contact)?;
+                Ok(Return::Nothing)
+            }
+            Command::Key { .. } | Command::Text(_) => Ok(Return::Nothing),
+            Command::Gesture(gesture) => self.gesture(&gesture),
+            Command::SetSwipeProfile(profile) => {
+                self.swipe = profile;
+                Ok(Return::Nothing)
+            }
+            Command::SetInputSpace(input) => {
+                self.input = input;
+                Ok(Return::Nothing)
+            }
+            command @ (Command::TabAt(_)
+            | Command::LongPressAt { .. }
+            | Command::ScrollAt { .. }) => self.dispatch(command.resolve(&space)?),
+            Command::ControlScreenCapture { start } => {
+                Ok(self.capture.control_screen_capture(start)?)
+            }
+            Command::TestScreenShotDelay {} => self.capture.test_screen_shot_delay(),
+            Command::SetFrameRate(rate) => Ok(self.capture.set_frame_rate(rate)?),
+            Command::BindDisplay(target) => match target {
+                DisplayTarget::Id(id) => Ok(Return::DisplayId(id)),
+                DisplayTarget::Package { .. } => Ok(Return::DisplayId(0)),
+            },
+        }
+    }
+}
+
+impl MockController {
+    pub fn log(&self) -> &CommandLog {
+        &self.log
+    }
+}
+
+impl MockController {
+    pub fn gesture(&mut self, gesture: &Gesture) -> Result<Return, MockError> {
+        self.check_gesture(gesture)?;
+        let timeline = gesture.timeline(self.swipe.rate)?;
+        run_timeline(self, &timeline)?;
+
+        Ok(Return::Nothing)
+    }
+}
+
+impl HoldTouch for MockController {
+    fn held(&mut self) -> &mut BTreeSet<u32> {
+        &mut self.held
+    }
+}
+
+impl Drop for MockController {
+    fn drop(&mut self) {
+        let _ = self.release_held();
+    }
+}
+
+impl TouchSink for MockController {
+    type Error = MockError;
+
+    fn touch_down(&mut self, contact: u32, x: i32, y: i32) -> Result<(), MockError> {
+        self.log.push_touch(TouchEvent::Down { contact, x, y });
+        self.touches.lock().unwrap().insert(contact, ((x, y), None));
+        Ok(())
+    }
+
+    fn touch_move(&mut self, contact: u32, x: i32, y: i32) -> Result<(), MockError> {
+        self.log.push_touch(TouchEvent::Move { contact, x, y });
+        self.touches.lock().unwrap().insert(contact, ((x, y), None));
+        Ok(())
+    }
+
+    fn touch_up(&mut self, contact: u32) -> Result<(), MockError> {
+        self.log.push_touch(TouchEvent::Up { contact });
+        if let Some((_, lifted)) = self.touches.lock().unwrap().get_mut(&contact) {
+            *lifted = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use anyhow::{Result, anyhow};
+    use image::Rgba;
+
+    use super::*;
+    use crate::{
+        Controller, ControllerError, KeyAction, KeyCode, Platform, SubscriberConfig, controller,
+    };
+    use mtas_utils::{CapturePoint, InputPoint, Normalized, Rotation, Size};
+
+    fn solid(width: u32, height: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba([value, value, value, 255]))
+    }
+
+    fn wait_frame(screen_cap: &mut ScreenCapture) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !screen_cap.update() {
+            if Instant::now() > deadline {
+                return Err(anyhow!("no frame published"));
+            }
+            sleep(Duration::from_millis(1));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_serves_frames() -> Result<()> {
+        let mut config = MockConfig::from_images(vec![solid(8, 4, 10), solid(8, 4, 20)]);
+        config.frame_interval = Duration::from_millis(1);
+
+        let (mut controller, mut screen_cap) = controller(Platform::Mock(config))?;
+        assert_eq!((screen_cap.width, screen_cap.height), (8, 4));
+
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+        wait_frame(&mut screen_cap)?;
+
+        let img = screen_cap.get_screen()?;
+        assert_eq!(img.dimensions(), (8, 4));
+        assert!([10, 20].contains(&img.get_pixel(3, 2)[0]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_shared_frames() -> Result<()> {
+        let mut config = MockConfig::from_images(vec![solid(4, 2, 10), solid(4, 2, 20)]);
+        config.frame_interval = Duration::from_millis(1);
+
+        let (mut controller, mut screen_cap) = controller(Platform::Mock(config))?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+        wait_frame(&mut screen_cap)?;
+
+        let held = screen_cap.latest();
+        let value = held.data[0];
+
+        // Later frames do not touch a frame that is still held
+        for _ in 0..3 {
+            screen_cap
+                .wait_new_frame(Duration::from_secs(2))
+                .ok_or_else(|| anyhow!("no frame"))?;
+        }
+        assert!(screen_cap.latest().meta.seq > held.meta.seq);
+        assert!(held.data.iter().step_by(4).all(|v| *v == value));
+
+        let view = screen_cap.view()?;
+        assert_eq!(view.dimensions(), (4, 2));
+        assert!([10, 20].contains(&view.get_pixel(1, 1)[0]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_hub_subscribers() -> Result<()> {
+        let mut config = MockConfig::from_images(vec![solid(2, 2, 10)]);
+        config.frame_interval = Duration::from_millis(1);
+
+        let (mut controller, screen_cap) = controller(Platform::Mock(config))?;
+        let readers: Vec<_> = (0..2)
+            .map(|_| {
+                let mut sub = screen_cap.subscribe(SubscriberConfig::default());
+                std::thread::spawn(move || {
+                    (0..3)
+                        .filter_map(|_| sub.next(Duration::from_secs(2)))
+                        .map(|frame| frame.meta.seq)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+
+        for reader in readers {
+            let seqs = reader
+                .join()
+                .map_err(|_| anyhow!("reader thread panicked"))?;
+            assert_eq!(seqs.len(), 3);
+            assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_frame_sequence() -> Result<()> {
+        let mut config = MockConfig::from_images(vec![solid(2, 2, 10), solid(2, 2, 20)]);
+        config.frame_interval = Duration::from_millis(100);
+
+        let (mut controller, mut screen_cap) = controller(Platform::Mock(config))?;
+        assert!(
+            screen_cap
+                .wait_new_frame(Duration::from_millis(20))
+                .is_none()
+        );
+
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+
+        let first = screen_cap
+            .wait_new_frame(Duration::from_secs(2))
+            .ok_or_else(|| anyhow!("no first frame"))?
+            .meta;
+        assert!(first.seq >= 1);
+        assert!(first.finished >= first.started);
+        assert_eq!((first.width, first.height), (2, 2));
+
+        // The next one is still being captured
+        assert!(screen_cap.latest_if_newer(first.seq).is_none());
+
+        let second = screen_cap
+            .wait_new_frame(Duration::from_secs(2))
+            .ok_or_else(|| anyhow!("no second frame"))?
+            .meta;
+        assert_eq!(second.seq, first.seq + 1);
+        assert!(second.started >= first.finished);
+        assert!(screen_cap.latest_if_newer(first.seq).is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_records_commands() -> Result<()> {
+        let config = MockConfig::from_images(vec![solid(200, 200, 0)]);
+        let log = config.log.clone();
+
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config))?;
+
+        controller.execute(Command::Tab { x: 1, y: 2 })?;
+        controller.execute(Command::Scroll {
+            x1: 0,
+            y1: 0,
+            x2: 1,
+            y2: 1,
+            t: Duration::from_millis(5),
+        })?;
+
+        assert!(matches!(
+            controller.execute(Command::BindDisplay(DisplayTarget::Id(2)))?,
+            Return::DisplayId(2)
+        ));
+        controller.execute(Command::Key {
+            code: KeyCode::Back,
+            action: KeyAction::Press,
+        })?;
+        controller.execute(Command::Text("hello".to_string()))?;
+
+        let commands = log.commands();
+        assert_eq!(commands.len(), 5);
+        assert!(matches!(commands[0], Command::Tab { x: 1, y: 2 }));
+        assert!(matches!(commands[1], Command::Scroll { x2: 1, .. }));
+
+        let Controller::Mock(mock) = &controller else {
+            return Err(anyhow!("not a mock controller"));
+        };
+        assert!(matches!(
+            commands[3],
+            Command::Key {
+                code: KeyCode::Back,
+                ..
+            }
+        ));
+        assert!(matches!(&commands[4], Command::Text(text) if text == "hello"));
+        assert_eq!(mock.log().commands().len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_points() -> Result<()> {
+        let config = MockConfig::from_images(vec![solid(200, 100, 0)]);
+        let log = config.log.clone();
+
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config))?;
+
+        controller.execute(Command::TabAt(Normalized::new(0.5, 0.25).into()))?;
+        assert_eq!(
+            log.touches()[0],
+            TouchEvent::Down {
+                contact: 0,
+                x: 100,
+                y: 25
+            }
+        );
+
+        // Portrait panel at twice the frame resolution, frames turned clockwise
+        controller.execute(Command::SetInputSpace(InputSpace {
+            size: Some(Size::new(200, 400)),
+            rotation: Rotation::R90,
+            ..Default::default()
+        }))?;
+        log.clear();
+        controller.execute(Command::TabAt(CapturePoint::new(199, 0).into()))?;
+        assert_eq!(
+            log.touches()[0],
+            TouchEvent::Down {
+                contact: 0,
+                x: 1,
+                y: 1
+            }
+        );
+
+        assert!(matches!(
+            controller.execute(Command::TabAt(CapturePoint::new(200, 0).into())),
+            Err(ControllerError::MockError(MockError::Coord(
+                CoordError::OutOfBounds { .. }
+            )))
+        ));
+        assert!(matches!(
+            controller.execute(Command::TabAt(InputPoint::new(0, 400).into())),
+            Err(ControllerError::MockError(MockError::Coord(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_raw_points_checked() -> Result<()> {
+        let config = MockConfig::from_images(vec![solid(200, 100, 0)]);
+        let log = config.log.clone();
+
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config))?;
+
+        let outside = [
+            Command::Tab { x: 200, y: 0 },
+            Command::LongPress {
+                x: 0,
+                y: -1,
+                hold: Duration::ZERO,
+            },
+            Command::Scroll {
+                x1: 0,
+                y1: 0,
+                x2: 0,
+                y2: 100,
+                t: Duration::ZERO,
+            },
+            Command::TouchDown {
+                contact: 0,
+                x: 500,
+                y: 500,
+            },
+            Command::Gesture(Gesture::pinch_out((150, 50), 80, Duration::from_millis(10))),
+        ];
+        for command in outside {
+            let e = controller
+                .execute(command)
+                .err()
+                .ok_or_else(|| anyhow!("point outside the display was accepted"))?;
+            assert_eq!(e.class(), ErrorClass::InvalidArgument);
+        }
+        assert!(log.touches().is_empty());
+
+        // Bounds follow the input space, not the frame
+        controller.execute(Command::SetInputSpace(InputSpace {
+            size: Some(Size::new(400, 200)),
+            ..Default::default()
+        }))?;
+        controller.execute(Command::Tab { x: 399, y: 199 })?;
+        assert!(controller.execute(Command::Tab { x: 400, y: 0 }).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_hold_and_release() -> Result<()> {
+        let config = MockConfig::from_images(vec![solid(200, 200, 0)]);
+        let log = config.log.clone();
+
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config))?;
+
+        controller.execute(Command::TouchDown {
+            contact: 0,
+            x: 1,
+            y: 1,
+        })?;
+        controller.execute(Command::LongPress {
+            x: 5,
+            y: 5,
+            hold: Duration::from_millis(20),
+        })?;
+        controller.execute(Command::TouchMove {
+            contact: 0,
+            x: 2,
+            y: 2,
+        })?;
+        controller.execute(Command::TouchDown {
+            contact: 3,
+            x: 7,
+            y: 7,
+        })?;
+
+        // A failing command lets go of every held finger
+        assert!(controller.execute(Command::TouchUp { contact: 9 }).is_err());
+
+        controller.execute(Command::TouchDown {
+            contact: 4,
+            x: 0,
+            y: 0,
+        })?;
+        drop(controller);
+
+        assert_eq!(
+            log.touches(),
+            vec![
+                TouchEvent::Down {
+                    contact: 0,
+                    x: 1,
+                    y: 1
+                },
+                TouchEvent::Down {
+                    contact: 1,
+                    x: 5,
+                    y: 5
+                },
+                TouchEvent::Up { contact: 1 },
+                TouchEvent::Move {
+                    contact: 0,
+                    x: 2,
+                    y: 2
+                },
+                TouchEvent::Down {
+                    contact: 3,
+                    x: 7,
+                    y: 7
+                },
+                TouchEvent::Up { contact: 0 },
+                TouchEvent::Up { contact: 3 },
+                TouchEvent::Down {
+                    contact: 4,
+                    x: 0,
+                    y: 0
+                },
+                TouchEvent::Up { contact: 4 },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_tab_keeps_held() -> Result<()> {
+        let config = MockConfig::from_images(vec![solid(200, 200, 0)]);
+        let log = config.log.clone();
+
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config))?;
+
+        controller.execute(Command::TouchDown {
+            contact: 0,
+            x: 1,
+            y: 1,
+        })?;
+        controller.execute(Command::Tab { x: 5, y: 5 })?;
+        controller.execute(Command::Scroll {
+            x1: 0,
+            y1: 0,
+            x2: 0,
+            y2: 0,
+            t: Duration::ZERO,
+        })?;
+
+        // Contact 0 is still down and can be moved and released
+        controller.execute(Command::TouchMove {
+            contact: 0,
+            x: 2,
+            y: 2,
+        })?;
+        controller.execute(Command::TouchUp { contact: 0 })?;
+
+        let touches = log.touches();
+        let up = touches
+            .iter()
+            .position(|t| *t == TouchEvent::Up { contact: 0 });
+        assert_eq!(up, Some(touches.len() - 1));
+        assert!(touches[1..touches.len() - 2].iter().all(|t| match *t {
+            TouchEvent::Down { contact, .. }
+            | TouchEvent::Move { contact, .. }
+            | TouchEvent::Up { contact } => contact == 1,
+        }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_timed_swipe() -> Result<()> {
+        let config = MockConfig::from_images(vec![solid(200, 200, 0)]);
+        let log = config.log.clone();
+
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config))?;
+
+        controller.execute(Command::SetSwipeProfile(SwipeProfile {
+            easing: crate::touch::Easing::EaseInOut,
+            rate: 100.0,
+        }))?;
+
+        let start = Instant::now();
+        controller.execute(Command::Scroll {
+            x1: 0,
+            y1: 0,
+            x2: 100,
+            y2: 0,
+            t: Duration::from_millis(40),
+        })?;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+
+        let touches = log.touches();
+        assert_eq!(touches.len(), 6);
+        assert_eq!(
+            touches[0],
+            TouchEvent::Down {
+                contact: 0,
+                x: 0,
+                y: 0
+            }
+        );
+        assert_eq!(
+            touches[1],
+            TouchEvent::Move {
+                contact: 0,
+                x: 13,
+                y: 0
+            }
+        );
+        assert_eq!(
+            touches[4],
+            TouchEvent::Move {
+                contact: 0,
+                x: 100,
+                y: 0
+            }
+        );
+        assert_eq!(touches[5], TouchEvent::Up { contact: 0 });
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_gesture() -> Result<()> {
+        let config = MockConfig::from_images(vec![solid(200, 200, 0)]);
+        let log = config.log.clone();
+
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config))?;
+
+        controller.execute(Command::Gesture(Gesture::pinch_out(
+            (100, 100),
+            80,
+            Duration::from_millis(30),
+        )))?;
+
+        let touches = log.touches();
+        assert_eq!(
+            touches
+                .iter()
+                .filter(|e| matches!(e, TouchEvent::Down { .. }))
+                .count(),
+            2
+        );
+        assert_eq!(
+            &touches[touches.len() - 4..],
+            &[
+                TouchEvent::Move {
+                    contact: 0,
+                    x: 20,
+                    y: 100
+                },
+                TouchEvent::Up { contact: 0 },
+                TouchEvent::Move {
+                    contact: 1,
+                    x: 180,
+                    y: 100
+                },
+                TouchEvent::Up { contact: 1 },
+            ]
+        );
+
+        assert!(matches!(
+            controller.execute(Command::Gesture(Gesture::default())),
+            Err(crate::ControllerError::MockError(MockError::Gesture(_)))
+        ));
+
+        // A held finger is not taken over by a gesture
+        controller.execute(Command::TouchDown {
+            contact: 1,
+            x: 0,
+            y: 0,
+        })?;
+        let before = log.touches().len();
+        assert!(matches!(
+            controller.execute(Command::Gesture(Gesture::pinch_in(
+                (100, 100),
+                80,
+                Duration::from_millis(10),
+            ))),
+            Err(crate::ControllerError::MockError(MockError::Hold(
+                HoldError::AlreadyDown(1)
+            )))
+        ));
+        assert!(
+            !log.touches()[before..]
+                .iter()
+                .any(|e| matches!(e, TouchEvent::Down { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_screen_shot_delay() -> Result<()> {
+        let mut config = MockConfig::from_images(vec![solid(2, 2, 0)]);
+        config.frame_interval = Duration::from_millis(2);
+
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config))?;
+
+        match controller.execute(Command::TestScreenShotDelay {})? {
+            Return::Delay(delay) => assert!(delay >= Duration::from_millis(2)),
+            other => return Err(anyhow!("unexpected return {:?}", other)),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_frames_from_dir() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("mtas-mock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        solid(3, 3, 1).save(dir.join("0.png"))?;
+        solid(3, 3, 2).save(dir.join("1.png"))?;
+        std::fs::write(dir.join("notes.txt"), "ignored")?;
+
+        let images = load_frames(MockFrames::Dir(dir.clone()))?;
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[1].get_pixel(0, 0)[0], 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_rejects_mismatched_frames() {
+        let config = MockConfig::from_images(vec![solid(2, 2, 0), solid(3, 2, 0)]);
+
+        assert!(matches!(
+            MockController::new(config),
+            Err(MockError::FrameSizeMismatch { index: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,331 @@
+use std::path::{Path, PathBuf};
+
+use crate::{DisplayTarget, mumu::MuMuError};
+
+use tracing::*;
+
+/// Where and how to connect to a MuMu player.
+///
+/// Unset paths are discovered with [`discover_install`].
+#[derive(Clone, Debug, Default)]
+pub struct MuMuConfig {
+    /// Emulator install dir, passed to `nemu_connect`.
+    pub install_dir: Option<PathBuf>,
+    /// `external_renderer_ipc.dll`, probed below `install_dir` when unset.
+    pub dll_path: Option<PathBuf>,
+    /// Multi-instance index, the main instance is 0.
+    pub instance: i32,
+    pub display: DisplayTarget,
+}
+
+/// A located MuMu installation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MuMuInstall {
+    pub install_dir: PathBuf,
+    pub dll_path: PathBuf,
+}
+
+impl MuMuConfig {
+    pub const ENV_INSTALL_DIR: &'static str = "MTAS_MUMU_INSTALL_DIR";
+    pub const ENV_DLL_PATH: &'static str = "MTAS_MUMU_DLL";
+    pub const ENV_INSTANCE: &'static str = "MTAS_MUMU_INSTANCE";
+    pub const ENV_DISPLAY_ID: &'static str = "MTAS_MUMU_DISPLAY_ID";
+    /// Bind to the display of this app instead of `ENV_DISPLAY_ID` (needs MuMu keep-alive).
+    pub const ENV_PACKAGE: &'static str = "MTAS_MUMU_PACKAGE";
+    pub const ENV_APP_INDEX: &'static str = "MTAS_MUMU_APP_INDEX";
+
+    pub fn from_env() -> Result<Self, MuMuError> {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    /// Build a config from `var`, which returns the value of the given `MTAS_MUMU_*` key.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, MuMuError> {
+        fn parse<T: std::str::FromStr>(
+            key: &'static str,
+            value: Option<String>,
+        ) -> Result<Option<T>, MuMuError> {
+            value
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|_| MuMuError::InvalidConfig { key, value })
+                })
+                .transpose()
+        }
+
+        let display = match var(Self::ENV_PACKAGE) {
+            Some(name) => DisplayTarget::Package {
+                name,
+                app_index: parse(Self::ENV_APP_INDEX, var(Self::ENV_APP_INDEX))?.unwrap_or(0),
+            },
+            None => DisplayTarget::Id(
+                parse(Self::ENV_DISPLAY_ID, var(Self::ENV_DISPLAY_ID))?.unwrap_or(0),
+            ),
+        };
+
+        Ok(MuMuConfig {
+            install_dir: var(Self::ENV_INSTALL_DIR).map(PathBuf::from),
+            dll_path: var(Self::ENV_DLL_PATH).map(PathBuf::from),
+            instance: parse(Self::ENV_INSTANCE, var(Self::ENV_INSTANCE))?.unwrap_or(0),
+            display,
+        })
+    }
+
+    /// Resolve the install dir and dll, probing [`DEFAULT_INSTALL_DIRS`] if needed. A dll path
+    /// alone is enough, the install dir is then taken from where the dll sits.
+    pub fn locate(&self) -> Result<MuMuInstall, MuMuError> {
+        match (&self.install_dir, &self.dll_path) {
+            (Some(install_dir), Some(dll_path)) => Ok(MuMuInstall {
+                install_dir: install_dir.clone(),
+                dll_path: dll_path.clone(),
+            }),
+            (Some(install_dir), None) => discover_install([install_dir.as_path()]),
+            (None, Some(dll_path)) => Ok(MuMuInstall {
+                install_dir: install_dir_of(dll_path),
+                dll_path: dll_path.clone(),
+            }),
+            (None, None) => discover_install(DEFAULT_INSTALL_DIRS.iter().map(Path::new)),
+        }
+    }
+}
+
+/// Install locations used by the MuMu 12 and MuMu X installers.
+pub const DEFAULT_INSTALL_DIRS: &[&str] = &[
+    "C:\\Program Files\\Netease\\MuMuPlayer-12.0",
+    "C:\\Program Files\\Netease\\MuMu Player 12",
+    "C:\\Program Files\\Netease\\MuMuPlayer",
+    "C:\\Program Files\\Netease\\MuMu",
+    "D:\\Program Files\\Netease\\MuMuPlayer-12.0",
+];
+
+/// Where `external_renderer_ipc.dll` lives relative to the install dir, newest layout first.
+/// `*` matches a version dir, the highest version wins.
+const DLL_LAYOUTS: &[&[&str]] = &[
+    // MuMu 12 4.x and MuMu X
+    &["nx_main", "sdk"],
+    // MuMu 12 with versioned device dirs
+    &["nx_device", "*", "shell", "sdk"],
+    // MuMu 12 before the nx split
+    &["shell", "sdk"],
+];
+
+const DLL_NAME: &str = "external_renderer_ipc.dll";
+
+/// Return the first of `candidates` that contains a known MuMu layout.
+pub fn discover_install<'a>(
+    candidates: impl IntoIterator<Item = &'a Path>,
+) -> Result<MuMuInstall, MuMuError> {
+    let mut searched = Vec::new();
+
+    for install_dir in candidates {
+        if let Some(dll_path) = probe_dll(install_dir) {
+            info!("Found MuMu at {:?}", install_dir);
+            return Ok(MuMuInstall {
+                install_dir: install_dir.to_path_buf(),
+                dll_path,
+            });
+        }
+        searched.push(install_dir.to_path_buf());
+    }
+
+    Err(MuMuError::InstallNotFound { searched })
+}
+
+/// The install dir `dll` belongs to, going by [`DLL_LAYOUTS`]. A dll outside every known
+/// layout is taken to sit in the install dir itself.
+fn install_dir_of(dll: &Path) -> PathBuf {
+    let dir = dll.parent().unwrap_or(Path::new(""));
+    let segments: Vec<_> = dir.components().map(|c| c.as_os_str()).collect();
+
+    DLL_LAYOUTS
+        .iter()
+        .find_map(|layout| {
+            let base = segments.len().checked_sub(layout.len())?;
+            let matches = layout
+                .iter()
+                .zip(&segments[base..])
+                .all(|(expected, segment)| *expected == "*" || *segment == *expected);
+            if !matches {
+                return None;
+            }
+            dir.ancestors().nth(layout.len()).map(Path::to_path_buf)
+        })
+        .unwrap_or_else(|| dir.to_path_buf())
+}
+
+/// Find `external_renderer_ipc.dll` below `install_dir`.
+pub fn probe_dll(install_dir: &Path) -> Option<PathBuf> {
+    DLL_LAYOUTS
+        .iter()
+        .find_map(|layout| probe_layout(install_dir.to_path_buf(), layout))
+}
+
+fn probe_layout(dir: PathBuf, layout: &[&str]) -> Option<PathBuf> {
+    let Some((segment, rest)) = layout.split_first() else {
+        let dll = dir.join(DLL_NAME);
+        return dll.is_file().then_some(dll);
+    };
+
+    if *segment != "*" {
+        return probe_layout(dir.join(segment), rest);
+    }
+
+    let mut versions = std::fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    versions.sort_by_key(|v| std::cmp::Reverse(version_key(v)));
+
+    versions
+        .into_iter()
+        .find_map(|version| probe_layout(dir.join(version), rest))
+}
+
+/// `12.0` < `12.10`, non numeric parts sort as 0.
+fn version_key(version: &str) -> Vec<u32> {
+    version
+        .split(['.', '-', '_'])
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    struct FakeTree(PathBuf);
+
+    impl FakeTree {
+        fn new(name: &str) -> Result<Self> {
+            let root =
+                std::env::temp_dir().join(format!("mtas-mumu-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root)?;
+            Ok(FakeTree(root))
+        }
+
+        fn touch(&self, rel: &[&str]) -> Result<PathBuf> {
+            let path = rel.iter().fold(self.0.clone(), |p, s| p.join(s));
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, b"")?;
+            Ok(path)
+        }
+    }
+
+    impl Drop for FakeTree {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_probe_layouts() -> Result<()> {
+        let tree = FakeTree::new("layouts")?;
+
+        let old = tree.touch(&["old", "shell", "sdk", DLL_NAME])?;
+        assert_eq!(probe_dll(&tree.0.join("old")), Some(old));
+
+        tree.touch(&["versioned", "nx_device", "9.0", "shell", "sdk", DLL_NAME])?;
+        let newest = tree.touch(&["versioned", "nx_device", "12.0", "shell", "sdk", DLL_NAME])?;
+        std::fs::create_dir_all(tree.0.join("versioned/nx_device/13.0"))?;
+        assert_eq!(probe_dll(&tree.0.join("versioned")), Some(newest));
+
+        tree.touch(&["both", "shell", "sdk", DLL_NAME])?;
+        let main = tree.touch(&["both", "nx_main", "sdk", DLL_NAME])?;
+        assert_eq!(probe_dll(&tree.0.join("both")), Some(main));
+
+        std::fs::create_dir_all(tree.0.join("empty/nx_main/sdk"))?;
+        assert_eq!(probe_dll(&tree.0.join("empty")), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_discover_install() -> Result<()> {
+        let tree = FakeTree::new("discover")?;
+        let missing = tree.0.join("missing");
+        let found = tree.0.join("found");
+        let dll = tree.touch(&["found", "nx_main", "sdk", DLL_NAME])?;
+
+        let install = discover_install([missing.as_path(), found.as_path()])?;
+        assert_eq!(
+            install,
+            MuMuInstall {
+                install_dir: found,
+                dll_path: dll,
+            }
+        );
+
+        match discover_install([missing.as_path()]) {
+            Err(MuMuError::InstallNotFound { searched }) => assert_eq!(searched, vec![missing]),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_locate_from_dll() -> Result<()> {
+        let locate = |dll: &str| {
+            MuMuConfig {
+                dll_path: Some(PathBuf::from(dll)),
+                ..Default::default()
+            }
+            .locate()
+        };
+
+        let install = locate("/mumu/nx_device/12.0/shell/sdk/external_renderer_ipc.dll")?;
+        assert_eq!(install.install_dir, PathBuf::from("/mumu"));
+        let install = locate("/mumu/nx_main/sdk/external_renderer_ipc.dll")?;
+        assert_eq!(install.install_dir, PathBuf::from("/mumu"));
+        let install = locate("/somewhere/external_renderer_ipc.dll")?;
+        assert_eq!(install.install_dir, PathBuf::from("/somewhere"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_from_vars() -> Result<()> {
+        let config = MuMuConfig::from_vars(|key| match key {
+            MuMuConfig::ENV_INSTALL_DIR => Some("E:\\MuMu".to_string()),
+            MuMuConfig::ENV_INSTANCE => Some(" 2 ".to_string()),
+            _ => None,
+        })?;
+        assert_eq!(config.install_dir, Some(PathBuf::from("E:\\MuMu")));
+        assert_eq!(config.dll_path, None);
+        assert_eq!(config.instance, 2);
+        assert_eq!(config.display, DisplayTarget::Id(0));
+
+        let config = MuMuConfig::from_vars(|key| match key {
+            MuMuConfig::ENV_DISPLAY_ID => Some("3".to_string()),
+            MuMuConfig::ENV_PACKAGE => Some("com.example.game".to_string()),
+            MuMuConfig::ENV_APP_INDEX => Some("1".to_string()),
+            _ => None,
+        })?;
+        assert_eq!(
+            config.display,
+            DisplayTarget::Package {
+                name: "com.example.game".to_string(),
+                app_index: 1,
+            }
+        );
+
+        let bad = MuMuConfig::from_vars(|key| {
+            (key == MuMuConfig::ENV_DISPLAY_ID).then(|| "main".to_string())
+        });
+        assert!(matches!(
+            bad,
+            Err(MuMuError::InvalidConfig {
+                key: MuMuConfig::ENV_DISPLAY_ID,
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use std::{path::PathBuf, sync::mpsc::SendError};
+
+use crate::{
+    CaptureTimeout, ErrorClass, KeyCode, ScreenCapCommand,
+    touch::{GestureError, HoldError},
+};
+use mtas_utils::CoordError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MuMuError {
+    #[error("MuMu Player Not Found：{0}")]
+    PathNotFound(#[from] libloading::Error),
+
+    #[error("MuMu Player Not Found, searched: {searched:?}")]
+    InstallNotFound { searched: Vec<PathBuf> },
+
+    #[error("Invalid MuMu Config {key}: {value:?}")]
+    InvalidConfig { key: &'static str, value: String },
+
+    #[error("Display Size Kept Changing During Capture")]
+    DisplayResizing,
+
+    #[error("Key {0:?} Has No Linux Input Event Code")]
+    UnmappedKey(KeyCode),
+
+    #[error("MuMu Contact {contact} Out Of Range (max {max})")]
+    ContactOutOfRange { contact: u32, max: u32 },
+
+    #[error("Invalid Gesture: {0}")]
+    Gesture(#[from] GestureError),
+
+    #[error("Invalid Touch: {0}")]
+    Hold(#[from] HoldError),
+
+    #[error("Invalid Point: {0}")]
+    Coord(#[from] CoordError),
+
+    #[error("Screen Capture Command Failed to Send：{0}")]
+    ScreenCap(#[from] SendError<ScreenCapCommand>),
+
+    #[error("Screen Capture Timing Failed: {0}")]
+    CaptureTimeout(#[from] CaptureTimeout),
+
+    #[error("Nemu Connect Failed: {0}")]
+    NemuConnect(i32),
+
+    #[error("Nemu Get DisplayId Failed: {0}")]
+    NemuGetDisplayId(i32),
+
+    #[error("Nemu Capture Display Failed: {0}")]
+    NemuCaptureDisplay(i32),
+
+    #[error("Nemu Input Text Failed: {0}")]
+    NemuInputText(i32),
+
+    #[error("Nemu Input Event Touch Down Failed: {0}")]
+    NemuInputEventTouchDown(i32),
+
+    #[error("Nemu Input Event Touch Up Failed: {0}")]
+    NemuInputEventTouchUp(i32),
+
+    #[error("Nemu Input Event Key Down Failed: {0}")]
+    NemuInputEventKeyDown(i32),
+
+    #[error("Nemu Input Event Key Up Failed: {0}")]
+    NemuInputEventKeyUp(i32),
+
+    #[error("Nemu Input Event Finger Touch Down Failed: {0}")]
+    NemuInputEventFingerTouchDown(i32),
+
+    #[error("Nemu Input Event Finger Touch Up Failed: {0}")]
+    NemuInputEventFingerTouchUp(i32),
+}
+
+/// The header only promises a nonzero code on failure, so the call decides the class: a
+/// failing capture or input call means the handle went stale, e.g. the emulator restarted,
+/// while connecting and display lookup fail while the emulator or app is still starting.
+impl MuMuError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            MuMuError::PathNotFound(_)
+            | MuMuError::InstallNotFound { .. }
+            | MuMuError::InvalidConfig { .. } => ErrorClass::Fatal,
+            MuMuError::DisplayResizing
+            | MuMuError::NemuConnect(_)
+            | MuMuError::NemuGetDisplayId(_) => ErrorClass::Transient,
+            MuMuError::UnmappedKey(_)
+            | MuMuError::ContactOutOfRange { .. }
+            | MuMuError::Gesture(_)
+            | MuMuError::Hold(_)
+            | MuMuError::Coord(_) => ErrorClass::InvalidArgument,
+            MuMuError::ScreenCap(_)
+            | MuMuError::CaptureTimeout(_)
+            | MuMuError::NemuCaptureDisplay(_)
+            | MuMuError::NemuInputText(_)
+            | MuMuError::NemuInputEventTouchDown(_)
+            | MuMuError::NemuInputEventTouchUp(_)
+            | MuMuError::NemuInputEventKeyDown(_)
+            | MuMuError::NemuInputEventKeyUp(_)
+            | MuMuError::NemuInputEventFingerTouchDown(_)
+            | MuMuError::NemuInputEventFingerTouchUp(_) => ErrorClass::NeedsReconnect,
+        }
+    }
+
+    /// Whether the command never reached the emulator, see
+    /// [`crate::ControllerError::is_unsent`]. A failed nemu input call may have been applied.
+    pub fn is_unsent(&self) -> bool {
+        matches!(self, MuMuError::ScreenCap(_))
+    }
+}
+
+/// Fingers the finger touch calls take, as ids `1..=MAX_FINGERS`.
+pub const MAX_FINGERS: u32 = 10;
+
+/// The 1 based finger id of `contact`.
+pub fn finger_id(contact: u32) -> Result<i32, MuMuError> {
+    if contact >= MAX_FINGERS {
+        return Err(MuMuError::ContactOutOfRange {
+            contact,
+            max: MAX_FINGERS,
+        });
+    }
+    Ok(contact as i32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finger_id() {
+        assert!(matches!(finger_id(0), Ok(1)));
+        assert!(matches!(finger_id(9), Ok(10)));
+
+        let error = finger_id(10).err();
+        assert!(matches!(
+            error,
+            Some(MuMuError::ContactOutOfRange {
+                contact: 10,
+                max: 10
+            })
+        ));
+        assert_eq!(error.map(|e| e.class()), Some(ErrorClass::InvalidArgument));
+    }
+}
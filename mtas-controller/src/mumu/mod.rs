@@ -1 +1,3 @@
+mtas_macro::mod_flat!(config, error);
+#[cfg(windows)]
 mtas_macro::mod_flat!(mumu);
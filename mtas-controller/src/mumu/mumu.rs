@@ -1,193 +1,150 @@
 use std::{
-    ffi::OsStr,
+    collections::BTreeSet,
+    ffi::CString,
     os::windows::ffi::OsStrExt,
     sync::{
         Arc,
-        mpsc::{SendError, Sender, TryRecvError},
+        atomic::{AtomicU32, Ordering},
     },
-    time::{Duration, Instant},
+    time::Duration,
 };
 
-use crate::{Command, ControllerTrait, Return, ScreenCapture};
-use ringbuf::{
-    SharedRb,
-    storage::Heap,
-    traits::{Consumer, Producer, Split},
-    wrap::caching::Caching,
+use crate::{
+    CaptureHandle, Command, ControllerTrait, DisplayTarget, Frame, FrameSource, InputSpace,
+    KeyAction, KeyCode, Return, ScreenCapture,
+    mumu::{MuMuConfig, MuMuError, finger_id},
+    spawn_capture,
+    touch::{Gesture, HoldTouch, SwipeProfile, TouchSink, long_press, run_path, run_timeline},
 };
-use thiserror::Error;
-use triple_buffer::triple_buffer;
 
 use tracing::*;
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
-pub enum ScreenCapCommand {
-    CaptureEnabled(bool),
-    CaptureTimingEnabled(bool),
-}
-
 pub struct MuMuController {
-    screen_cmdtx: Sender<ScreenCapCommand>,
-    cons: Caching<Arc<SharedRb<Heap<Duration>>>, false, true>,
+    capture: CaptureHandle,
     lib: Arc<test>,
     connection: i32,
+    instance: i32,
+    /// Shared with the capture thread so rebinding moves capture and input together.
+    display_id: Arc<AtomicU32>,
+    swipe: SwipeProfile,
+    held: BTreeSet<u32>,
+    input: InputSpace,
 }
 
-#[derive(Error, Debug)]
-pub enum MuMuError {
-    #[error("MuMu Player Not Found：{0}")]
-    PathNotFound(#[from] libloading::Error),
-
-    #[error("Screen Capture Command Failed to Send：{0}")]
-    ScreenCap(#[from] SendError<ScreenCapCommand>),
-
-    #[error("Nemu Connect Failed: {0}")]
-    NemuConnect(i32),
-
-    #[error("Nemu Get DisplayId Failed: {0}")]
-    NemuGetDisplayId(i32),
-
-    #[error("Nemu Capture Display Failed: {0}")]
-    NemuCaptureDisplay(i32),
+struct MuMuFrameSource {
+    lib: Arc<test>,
+    connection: i32,
+    display_id: Arc<AtomicU32>,
+    width: i32,
+    height: i32,
+}
 
-    #[error("Nemu Input Text Failed: {0}")]
-    NemuInputText(i32),
+impl FrameSource for MuMuFrameSource {
+    type Error = MuMuError;
 
-    #[error("Nemu Input Event Touch Down Failed: {0}")]
-    NemuInputEventTouchDown(i32),
+    fn capture(&mut self, frame: &mut Frame) -> Result<(), MuMuError> {
+        // A second try once the buffer matches the size MuMu reported
+        for _ in 0..2 {
+            frame.resize(self.width as usize, self.height as usize);
+
+            let mut cur_width = self.width;
+            let mut cur_height = self.height;
+
+            let result = unsafe {
+                self.lib.nemu_capture_display(
+                    self.connection,
+                    self.display_id.load(Ordering::Relaxed),
+                    frame.data.len() as i32,
+                    &mut cur_width,
+                    &mut cur_height,
+                    frame.data.as_mut_ptr(),
+                )
+            };
+
+            // A stale connection can report garbage sizes, never allocate for them
+            if cur_width <= 0 || cur_height <= 0 {
+                return Err(MuMuError::NemuCaptureDisplay(result));
+            }
 
-    #[error("Nemu Input Event Touch Up Failed: {0}")]
-    NemuInputEventTouchUp(i32),
+            if (cur_width, cur_height) != (self.width, self.height) {
+                (self.width, self.height) = (cur_width, cur_height);
+                continue;
+            }
 
-    #[error("Nemu Input Event Key Down Failed: {0}")]
-    NemuInputEventKeyDown(i32),
+            if result != 0 {
+                return Err(MuMuError::NemuCaptureDisplay(result));
+            }
 
-    #[error("Nemu Input Event Key Up Failed: {0}")]
-    NemuInputEventKeyUp(i32),
+            return Ok(());
+        }
 
-    #[error("Nemu Input Event Finger Touch Down Failed: {0}")]
-    NemuInputEventFingerTouchDown(i32),
+        Err(MuMuError::DisplayResizing)
+    }
 
-    #[error("Nemu Input Event Finger Touch Up Failed: {0}")]
-    NemuInputEventFingerTouchUp(i32),
+    /// `nemu_capture_display` fills the buffer bottom row first.
+    fn bottom_up(&self) -> bool {
+        true
+    }
 }
 
 impl ControllerTrait for MuMuController {
+    type Config = MuMuConfig;
     type Error = MuMuError;
 
-    fn new() -> Result<(Self, ScreenCapture), MuMuError> {
-        let lib = Arc::new(unsafe {
-            test::new(
-                "D:\\Program\\mumu\\MuMu Player 12\\nx_device\\12.0\\shell\\sdk\\external_renderer_ipc.dll",
-            )?
-        });
+    fn new(config: MuMuConfig) -> Result<(Self, ScreenCapture), MuMuError> {
+        let install = config.locate()?;
+
+        info!("Loading {:?}", install.dll_path);
 
-        let install_path = "D:\\Program\\mumu\\MuMu Player 12";
-        let os_str = OsStr::new(install_path);
+        let lib = Arc::new(unsafe { test::new(&install.dll_path)? });
 
-        let wide_chars: Vec<u16> = os_str.encode_wide().chain(std::iter::once(0u16)).collect();
+        let wide_chars: Vec<u16> = install
+            .install_dir
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0u16))
+            .collect();
 
-        let connection = unsafe { lib.nemu_connect(wide_chars.as_ptr(), 0) };
+        let connection = unsafe { lib.nemu_connect(wide_chars.as_ptr(), config.instance) };
 
         if connection <= 0 {
             return Err(MuMuError::NemuConnect(connection));
         }
 
-        let mut width: i32 = 0;
-        let mut height: i32 = 0;
-
-        let result = unsafe {
-            lib.nemu_capture_display(
-                connection,
-                0,
-                0,
-                &mut width,
-                &mut height,
-                std::ptr::null_mut::<u8>(),
-            )
-        };
-
-        if result != 0 {
-            return Err(MuMuError::NemuCaptureDisplay(result));
-        }
-
-        let (mut input_buffer, output_buffer) =
-            triple_buffer(&vec![0u8; (width * height * 4) as usize]);
-
-        let screen_capture = ScreenCapture {
-            width: width as usize,
-            height: height as usize,
-            capture: output_buffer,
-        };
-
-        let (screen_cmdtx, screen_cmdrx) = std::sync::mpsc::channel::<ScreenCapCommand>();
-
-        let rb = SharedRb::<Heap<Duration>>::new(10);
-        let (mut prod, cons) = rb.split();
-
-        let lib_in = lib.clone();
-
-        std::thread::spawn(move || {
-            info!("Thread ScreenCap Begin");
-
-            let mut cur_width = width;
-            let mut cur_height = height;
-
-            let mut screen_on_in = false;
-            let mut capture_timing_enabled = false;
+        let display_id = resolve_display(&lib, connection, &config.display)?;
+        let (width, height) = display_size(&lib, connection, display_id)?;
 
-            loop {
-                let start = Instant::now();
+        info!(
+            "MuMu instance {} display {} is {}x{}",
+            config.instance, display_id, width, height
+        );
 
-                match screen_cmdrx.try_recv() {
-                    Ok(command) => match command {
-                        ScreenCapCommand::CaptureEnabled(on) => screen_on_in = on,
-                        ScreenCapCommand::CaptureTimingEnabled(on) => capture_timing_enabled = on,
-                    },
-                    Err(TryRecvError::Empty) => {}
-                    Err(TryRecvError::Disconnected) => break,
-                }
+        let display_id = Arc::new(AtomicU32::new(display_id));
 
-                if !screen_on_in {
-                    continue;
-                }
-
-                let result = unsafe {
-                    lib_in.nemu_capture_display(
-                        connection,
-                        0,
-                        (width * height * 4) as i32,
-                        &mut cur_width,
-                        &mut cur_height,
-                        input_buffer.input_buffer_mut().as_mut_ptr() as *mut u8,
-                    )
-                };
-
-                if cur_width != width || cur_height != height {
-                    panic!("Display size changed");
-                }
-
-                if result != 0 {
-                    panic!("Failed to capture display");
-                }
-
-                if capture_timing_enabled {
-                    let _ = prod.try_push(start.elapsed());
-                }
-
-                input_buffer.publish();
-            }
-
-            info!("Thread ScreenCap End");
-        });
+        let (capture, screen_capture) = spawn_capture(
+            MuMuFrameSource {
+                lib: lib.clone(),
+                connection,
+                display_id: display_id.clone(),
+                width,
+                height,
+            },
+            width as usize,
+            height as usize,
+        );
 
         Ok((
             MuMuController {
-                screen_cmdtx,
-                cons,
+                capture,
                 lib,
                 connection,
+                instance: config.instance,
+                display_id,
+                swipe: SwipeProfile::default(),
+                held: BTreeSet::new(),
+                input: InputSpace::default(),
             },
             screen_capture,
         ))
@@ -195,109 +152,271 @@ impl ControllerTrait for MuMuController {
 
     #[instrument(skip_all)]
     fn execute(&mut self, command: crate::Command) -> Result<Return, MuMuError> {
+        let result = self.dispatch(command);
+
+        if result.is_err()
+            && let Err(e) = self.release_held()
+        {
+            warn!("Failed to release held touches: {}", e);
+        }
+        result
+    }
+}
+
+impl MuMuController {
+    fn dispatch(&mut self, command: Command) -> Result<Return, MuMuError> {
+        let space = self.input.coord_space(self.capture.frame_size());
+        command.check(&space)?;
+
         match command {
             Command::Tab { x, y } => self.tab(x, y),
+            Command::LongPress { x, y, hold } => self.long_press(x, y, hold),
+            Command::TouchDown { contact, x, y } => {
+                self.hold_down(contact, x, y)?;
+                Ok(Return::Nothing)
+            }
+            Command::TouchMove { contact, x, y } => {
+                self.hold_move(contact, x, y)?;
+                Ok(Return::Nothing)
+            }
+            Command::TouchUp { contact } => {
+                self.hold_up(contact)?;
+                Ok(Return::Nothing)
+            }
             Command::Scroll { x1, y1, x2, y2, t } => self.scroll(x1, y1, x2, y2, t),
             Command::ControlScreenCapture { start } => self.control_screen_capture(start),
+            Command::Key { code, action } => self.key(code, action),
+            Command::Text(text) => self.text(&text),
+            Command::Gesture(gesture) => self.gesture(&gesture),
+            Command::SetSwipeProfile(profile) => {
+                self.swipe = profile;
+                Ok(Return::Nothing)
+            }
+            Command::SetInputSpace(input) => {
+                self.input = input;
+                Ok(Return::Nothing)
+            }
+            command @ (Command::TabAt(_)
+            | Command::LongPressAt { .. }
+            | Command::ScrollAt { .. }) => self.dispatch(command.resolve(&space)?),
+            Command::BindDisplay(target) => self.bind_display(&target),
             Command::TestScreenShotDelay {} => self.test_screen_shot_delay(),
+            Command::SetFrameRate(rate) => Ok(self.capture.set_frame_rate(rate)?),
         }
     }
 }
 
-impl MuMuController {
-    pub fn tab(&self, x: i32, y: i32) -> Result<Return, MuMuError> {
-        let result_down = unsafe {
-            self.lib
-                .nemu_input_event_touch_down(self.connection, 0, x, y)
-        };
-        if result_down != 0 {
-            Err(MuMuError::NemuInputEventTouchDown(result_down))
-        } else {
-            let result_up = unsafe { self.lib.nemu_input_event_touch_up(self.connection, 0) };
-            if result_up != 0 {
-                Err(MuMuError::NemuInputEventTouchUp(result_up))
-            } else {
-                Ok(Return::Nothing)
+fn resolve_display(lib: &test, connection: i32, target: &DisplayTarget) -> Result<u32, MuMuError> {
+    match target {
+        DisplayTarget::Id(id) => Ok(*id),
+        DisplayTarget::Package { name, app_index } => {
+            let pkg = CString::new(name.as_str()).map_err(|_| MuMuError::InvalidConfig {
+                key: MuMuConfig::ENV_PACKAGE,
+                value: name.clone(),
+            })?;
+
+            let id = unsafe { lib.nemu_get_display_id(connection, pkg.as_ptr(), *app_index) };
+            if id < 0 {
+                return Err(MuMuError::NemuGetDisplayId(id));
             }
+
+            Ok(id as u32)
         }
     }
+}
+
+fn display_size(lib: &test, connection: i32, display_id: u32) -> Result<(i32, i32), MuMuError> {
+    let mut width: i32 = 0;
+    let mut height: i32 = 0;
+
+    let result = unsafe {
+        lib.nemu_capture_display(
+            connection,
+            display_id,
+            0,
+            &mut width,
+            &mut height,
+            std::ptr::null_mut::<u8>(),
+        )
+    };
+
+    if result != 0 {
+        return Err(MuMuError::NemuCaptureDisplay(result));
+    }
+
+    Ok((width, height))
+}
+
+impl MuMuController {
+    pub fn instance(&self) -> i32 {
+        self.instance
+    }
+
+    pub fn display_id(&self) -> u32 {
+        self.display_id.load(Ordering::Relaxed)
+    }
+
+    fn display(&self) -> i32 {
+        self.display_id() as i32
+    }
+
+    /// Move capture and input to another display, e.g. after the bound app restarted.
+    pub fn bind_display(&self, target: &DisplayTarget) -> Result<Return, MuMuError> {
+        let display_id = resolve_display(&self.lib, self.connection, target)?;
+        // Fails early for a display that cannot be captured, the capture thread picks up
+        // a different size on its own
+        display_size(&self.lib, self.connection, display_id)?;
+
+        self.display_id.store(display_id, Ordering::Relaxed);
+
+        Ok(Return::DisplayId(display_id))
+    }
+
+    /// Uses a finger that is not held, the single touch api would clash with held ones.
+    pub fn tab(&mut self, x: i32, y: i32) -> Result<Return, MuMuError> {
+        let contact = self.free_contact();
+        self.touch_down(contact, x, y)?;
+        self.touch_up(contact)?;
+
+        Ok(Return::Nothing)
+    }
+
+    pub fn long_press(&mut self, x: i32, y: i32, hold: Duration) -> Result<Return, MuMuError> {
+        let contact = self.free_contact();
+        long_press(self, contact, x, y, hold)?;
+
+        Ok(Return::Nothing)
+    }
 
     pub fn scroll(
-        &self,
+        &mut self,
         x1: i32,
         y1: i32,
         x2: i32,
         y2: i32,
         t: Duration,
     ) -> Result<Return, MuMuError> {
-        let _ = t; // Ignore the duration for now
+        let contact = self.free_contact();
+        let path = self.swipe.path(x1, y1, x2, y2, t);
+        run_path(self, contact, &path)?;
 
-        let res_down = unsafe {
-            self.lib
-                .nemu_input_event_finger_touch_down(self.connection, 0, 1, x1, y1)
-        };
-        if res_down != 0 {
-            return Err(MuMuError::NemuInputEventFingerTouchDown(res_down));
+        Ok(Return::Nothing)
+    }
+
+    pub fn key(&self, code: KeyCode, action: KeyAction) -> Result<Return, MuMuError> {
+        let linux = code.linux().ok_or(MuMuError::UnmappedKey(code))?;
+
+        if action != KeyAction::Up {
+            let result = unsafe {
+                self.lib
+                    .nemu_input_event_key_down(self.connection, self.display(), linux)
+            };
+            if result != 0 {
+                return Err(MuMuError::NemuInputEventKeyDown(result));
+            }
         }
 
-        let res_down = unsafe {
-            self.lib
-                .nemu_input_event_finger_touch_down(self.connection, 0, 1, x2, y2)
-        };
-        if res_down != 0 {
-            return Err(MuMuError::NemuInputEventFingerTouchDown(res_down));
+        if action != KeyAction::Down {
+            let result = unsafe {
+                self.lib
+                    .nemu_input_event_key_up(self.connection, self.display(), linux)
+            };
+            if result != 0 {
+                return Err(MuMuError::NemuInputEventKeyUp(result));
+            }
         }
 
-        let res_up = unsafe {
+        Ok(Return::Nothing)
+    }
+
+    pub fn text(&self, text: &str) -> Result<Return, MuMuError> {
+        let result = unsafe {
             self.lib
-                .nemu_input_event_finger_touch_up(self.connection, 0, 1)
+                .nemu_input_text(self.connection, text.len() as i32, text.as_ptr().cast())
         };
-        if res_up != 0 {
-            return Err(MuMuError::NemuInputEventFingerTouchUp(res_up));
+        if result != 0 {
+            return Err(MuMuError::NemuInputText(result));
         }
 
         Ok(Return::Nothing)
     }
 
-    pub fn control_screen_capture(&self, start: bool) -> Result<Return, MuMuError> {
-        self.screen_cmdtx
-            .send(ScreenCapCommand::CaptureEnabled(start))?;
+    pub fn gesture(&mut self, gesture: &Gesture) -> Result<Return, MuMuError> {
+        self.check_gesture(gesture)?;
+        // Checked up front, a finger out of range must not cut a gesture short halfway
+        for track in &gesture.tracks {
+            finger_id(track.contact)?;
+        }
+        let timeline = gesture.timeline(self.swipe.rate)?;
+        run_timeline(self, &timeline)?;
 
         Ok(Return::Nothing)
     }
 
-    pub fn control_screen_capture_timing(&self, start: bool) -> Result<Return, MuMuError> {
-        self.screen_cmdtx
-            .send(ScreenCapCommand::CaptureTimingEnabled(start))?;
+    pub fn control_screen_capture(&self, start: bool) -> Result<Return, MuMuError> {
+        Ok(self.capture.control_screen_capture(start)?)
+    }
 
-        Ok(Return::Nothing)
+    pub fn control_screen_capture_timing(&self, start: bool) -> Result<Return, MuMuError> {
+        Ok(self.capture.control_screen_capture_timing(start)?)
     }
 
     pub fn test_screen_shot_delay(&mut self) -> Result<Return, MuMuError> {
-        self.control_screen_capture(true)?;
-        self.control_screen_capture_timing(true)?;
-
-        let mut times = Vec::with_capacity(10);
-        for _ in 0..10 {
-            'innerloop: loop {
-                if let Some(time) = self.cons.try_pop() {
-                    times.push(time);
-                    break 'innerloop;
-                }
-            }
+        self.capture.test_screen_shot_delay()
+    }
+}
+
+/// Contact `n` is MuMu finger `n + 1`, a repeated finger down moves the finger.
+impl TouchSink for MuMuController {
+    type Error = MuMuError;
+
+    fn touch_down(&mut self, contact: u32, x: i32, y: i32) -> Result<(), MuMuError> {
+        let finger = finger_id(contact)?;
+        let result = unsafe {
+            self.lib.nemu_input_event_finger_touch_down(
+                self.connection,
+                self.display(),
+                finger,
+                x,
+                y,
+            )
+        };
+        if result != 0 {
+            return Err(MuMuError::NemuInputEventFingerTouchDown(result));
+        }
+        Ok(())
+    }
+
+    fn touch_move(&mut self, contact: u32, x: i32, y: i32) -> Result<(), MuMuError> {
+        self.touch_down(contact, x, y)
+    }
+
+    fn touch_up(&mut self, contact: u32) -> Result<(), MuMuError> {
+        let finger = finger_id(contact)?;
+        let result = unsafe {
+            self.lib
+                .nemu_input_event_finger_touch_up(self.connection, self.display(), finger)
+        };
+        if result != 0 {
+            return Err(MuMuError::NemuInputEventFingerTouchUp(result));
         }
+        Ok(())
+    }
+}
 
-        self.control_screen_capture_timing(false)?;
-        times.sort();
-        let trimmed = &times[1..(10 - 1)]; // Drop min & max (basic outlier trimming).
-        let total: Duration = trimmed.iter().sum();
-        let ave = total / trimmed.len() as u32;
-        Ok(Return::Delay(ave))
+impl HoldTouch for MuMuController {
+    fn held(&mut self) -> &mut BTreeSet<u32> {
+        &mut self.held
     }
 }
 
 impl Drop for MuMuController {
     fn drop(&mut self) {
+        if let Err(e) = self.release_held() {
+            warn!("Failed to release held touches: {}", e);
+        }
+        // The capture thread shares the connection, it has to be gone before disconnecting
+        self.capture.stop();
         unsafe { self.lib.nemu_disconnect(self.connection) };
     }
 }
@@ -320,7 +439,7 @@ mod tests {
 
         test(42, "Ferris");
 
-        let (mut controller, _screen_cap) = MuMuController::new()?;
+        let (mut controller, _screen_cap) = MuMuController::new(MuMuConfig::from_env()?)?;
 
         info!("{:?}", controller.execute(Command::TestScreenShotDelay {}));
 
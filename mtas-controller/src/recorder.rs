@@ -0,0 +1,485 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+use tracing::*;
+
+use crate::{Frame, ScreenCapture, SharedFrame, SubscriberConfig};
+
+/// What a [`ScreenRecorder`] does with the frames it receives.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordMode {
+    /// Write every frame as it arrives.
+    Continuous,
+    /// Keep the frames of the last given span in memory, written by [`ScreenRecorder::flush`].
+    Rolling(Duration),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecorderConfig {
+    pub mode: RecordMode,
+    /// Frames arriving faster than this are skipped.
+    pub max_fps: Option<f64>,
+    /// Most pixel bytes a rolling recorder keeps, older frames are dropped before the span
+    /// is up once it is reached. The newest frame is always kept.
+    pub max_bytes: usize,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        RecorderConfig {
+            mode: RecordMode::Continuous,
+            max_fps: Some(10.0),
+            max_bytes: 256 << 20,
+        }
+    }
+}
+
+impl RecorderConfig {
+    pub fn rolling(span: Duration) -> Self {
+        RecorderConfig {
+            mode: RecordMode::Rolling(span),
+            ..Default::default()
+        }
+    }
+}
+
+/// One line of a recording's [`ScreenRecorder::INDEX`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedFrame {
+    pub seq: u64,
+    /// Capture time since the first frame of the recording.
+    pub at: Duration,
+    pub path: PathBuf,
+}
+
+#[derive(Error, Debug)]
+pub enum RecorderError {
+    #[error("Recording Io Failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to Save Recorded Frame: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Image Container is Not Big Enough")]
+    Frame,
+
+    #[error("Invalid Recording Index Line: {0}")]
+    Index(String),
+
+    #[error("Recorder Thread Has Panicked")]
+    Panicked,
+}
+
+/// Frames kept by a rolling recorder, oldest first.
+#[derive(Default)]
+struct Rolling {
+    frames: VecDeque<SharedFrame>,
+    bytes: usize,
+}
+
+impl Rolling {
+    fn push(&mut self, frame: SharedFrame, span: Duration, max_bytes: usize) {
+        let newest = frame.meta.finished;
+        self.bytes += frame.data.len();
+        self.frames.push_back(frame);
+
+        while self.frames.len() > 1
+            && (self.bytes > max_bytes || self.frames[0].meta.finished + span < newest)
+        {
+            if let Some(oldest) = self.frames.pop_front() {
+                self.bytes -= oldest.data.len();
+            }
+        }
+    }
+
+    fn take(&mut self) -> Vec<SharedFrame> {
+        self.bytes = 0;
+        self.frames.drain(..).collect()
+    }
+}
+
+/// A png per frame plus an index of `seq`, milliseconds since the first frame and file name.
+struct Recording {
+    dir: PathBuf,
+    index: BufWriter<File>,
+    first: Option<Instant>,
+    written: usize,
+}
+
+impl Recording {
+    fn create(dir: &Path) -> Result<Self, RecorderError> {
+        std::fs::create_dir_all(dir)?;
+
+        // Frames of a recording there before would mix with this one
+        if let Ok(old) = read_recording(dir) {
+            for frame in old {
+                match std::fs::remove_file(&frame.path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(Recording {
+            dir: dir.to_path_buf(),
+            index: BufWriter::new(File::create(dir.join(ScreenRecorder::INDEX))?),
+            first: None,
+            written: 0,
+        })
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<(), RecorderError> {
+        let first = *self.first.get_or_insert(frame.meta.finished);
+        let at = frame.meta.finished.saturating_duration_since(first);
+        let name = format!("{:08}.png", frame.meta.seq);
+
+        frame
+            .to_rgba_image()
+            .ok_or(RecorderError::Frame)?
+            .save(self.dir.join(&name))?;
+
+        // Flushed per frame, so a crash keeps everything before it
+        writeln!(self.index, "{} {} {}", frame.meta.seq, at.as_millis(), name)?;
+        self.index.flush()?;
+
+        self.written += 1;
+        Ok(())
+    }
+}
+
+/// Records the frames of a [`ScreenCapture`] to a directory on a thread of its own.
+pub struct ScreenRecorder {
+    dir: PathBuf,
+    rolling: Arc<Mutex<Rolling>>,
+    flushed: usize,
+    stop: Arc<AtomicBool>,
+    failed: Arc<AtomicBool>,
+    worker: Option<JoinHandle<Result<usize, RecorderError>>>,
+}
+
+impl ScreenRecorder {
+    pub const INDEX: &'static str = "index.txt";
+
+    /// Start recording into `dir`, replacing the frames of a recording there before.
+    pub fn start(
+        screen_cap: &ScreenCapture,
+        dir: impl Into<PathBuf>,
+        config: RecorderConfig,
+    ) -> Result<Self, RecorderError> {
+        let dir = dir.into();
+        let mut recording = match config.mode {
+            RecordMode::Continuous => Some(Recording::create(&dir)?),
+            RecordMode::Rolling(_) => {
+                std::fs::create_dir_all(&dir)?;
+                None
+            }
+        };
+
+        let mut subscriber = screen_cap.subscribe(SubscriberConfig {
+            max_fps: config.max_fps,
+            history: 0,
+        });
+        let rolling = Arc::new(Mutex::new(Rolling::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let failed = Arc::new(AtomicBool::new(false));
+
+        let worker = {
+            let rolling = rolling.clone();
+            let stop = stop.clone();
+            let failed = failed.clone();
+            let dir = dir.clone();
+
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let Some(frame) = subscriber.next(Duration::from_millis(50)) else {
+                        continue;
+                    };
+
+                    if let Some(recording) = recording.as_mut() {
+                        if let Err(e) = recording.write(&frame) {
+                            error!("Screen recording to {:?} failed: {}", dir, e);
+                            failed.store(true, Ordering::Relaxed);
+                            return Err(e);
+                        }
+                    } else if let RecordMode::Rolling(span) = config.mode {
+                        rolling
+                            .lock()
+                            .unwrap()
+                            .push(frame, span, config.max_bytes);
+                    }
+                }
+
+                Ok(recording.map_or(0, |recording| recording.written))
+            })
+        };
+
+        info!("Recording screen to {:?} as {:?}", dir, config.mode);
+
+        Ok(ScreenRecorder {
+            dir,
+            rolling,
+            flushed: 0,
+            stop,
+            failed,
+            worker: Some(worker),
+        })
+    }
+
+    /// Whether writing a frame failed, recording stopped then and [`Self::stop`] returns why.
+    pub fn failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Write the frames kept by a rolling recorder to a new `flush-<n>` directory and forget
+    /// them, `None` if there were none.
+    pub fn flush(&mut self) -> Result<Option<PathBuf>, RecorderError> {
+        let frames = self.rolling.lock().unwrap().take();
+        if frames.is_empty() {
+            return Ok(None);
+        }
+
+        let dir = self.dir.join(format!("flush-{:03}", self.flushed));
+        self.flushed += 1;
+
+        let mut recording = Recording::create(&dir)?;
+        for frame in &frames {
+            recording.write(frame)?;
+        }
+
+        Ok(Some(dir))
+    }
+
+    /// Pass `result` through, flushing what led up to it if it is an error.
+    pub fn flush_on_error<T, E: std::fmt::Display>(
+        &mut self,
+        result: Result<T, E>,
+    ) -> Result<T, E> {
+        if let Err(e) = &result {
+            match self.flush() {
+                Ok(Some(dir)) => warn!("{}, last frames saved to {:?}", e, dir),
+                Ok(None) => warn!("{}, no frames to save", e),
+                Err(flush) => error!("{}, failed to save last frames: {}", e, flush),
+            }
+        }
+        result
+    }
+
+    /// Stop recording, returning how many frames a continuous recorder wrote.
+    pub fn stop(mut self) -> Result<usize, RecorderError> {
+        self.join().unwrap_or(Ok(0))
+    }
+
+    fn join(&mut self) -> Option<Result<usize, RecorderError>> {
+        self.stop.store(true, Ordering::Relaxed);
+        let worker = self.worker.take()?;
+        Some(worker.join().unwrap_or(Err(RecorderError::Panicked)))
+    }
+}
+
+impl Drop for ScreenRecorder {
+    fn drop(&mut self) {
+        // Write failures were logged by the worker as they happened
+        if let Some(Err(RecorderError::Panicked)) = self.join() {
+            error!("Screen recording failed: {}", RecorderError::Panicked);
+        }
+    }
+}
+
+/// Every frame of the recording in `dir`, oldest first.
+pub fn read_recording(dir: impl AsRef<Path>) -> Result<Vec<RecordedFrame>, RecorderError> {
+    let dir = dir.as_ref();
+    let file = File::open(dir.join(ScreenRecorder::INDEX))?;
+
+    let mut frames = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let invalid = || RecorderError::Index(line.clone());
+        let mut fields = line.split_whitespace();
+        let (Some(seq), Some(at), Some(name), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid());
+        };
+
+        frames.push(RecordedFrame {
+            seq: seq.parse().map_err(|_| invalid())?,
+            at: Duration::from_millis(at.parse().map_err(|_| invalid())?),
+            path: dir.join(name),
+        });
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Result, anyhow};
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    use super::*;
+    use crate::mock::MockConfig;
+    use crate::{Command, Platform, controller};
+
+    fn recording_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mtas-recorder-{}-{}", name, std::process::id()))
+    }
+
+    fn mock(frame_interval: Duration) -> MockConfig {
+        let mut config = MockConfig::from_images(vec![
+            RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255])),
+            RgbaImage::from_pixel(4, 4, Rgba([255, 255, 255, 255])),
+        ]);
+        config.frame_interval = frame_interval;
+        config
+    }
+
+    #[test]
+    fn test_record_continuous() -> Result<()> {
+        let dir = recording_dir("continuous");
+        let (mut controller, screen_cap) =
+            controller(Platform::Mock(mock(Duration::from_millis(5))))?;
+
+        let recorder = ScreenRecorder::start(
+            &screen_cap,
+            &dir,
+            RecorderConfig {
+                mode: RecordMode::Continuous,
+                max_fps: None,
+                ..Default::default()
+            },
+        )?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+        std::thread::sleep(Duration::from_millis(150));
+        let written = recorder.stop()?;
+        assert!(written >= 3);
+
+        let frames = read_recording(&dir)?;
+        assert_eq!(frames.len(), written);
+        assert_eq!(frames[0].at, Duration::ZERO);
+        for pair in frames.windows(2) {
+            assert!(pair[0].seq < pair[1].seq);
+            assert!(pair[0].at <= pair[1].at);
+        }
+        assert_eq!(image::open(&frames[0].path)?.dimensions(), (4, 4));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_rolling() -> Result<()> {
+        let dir = recording_dir("rolling");
+        let span = Duration::from_millis(40);
+        let (mut controller, screen_cap) =
+            controller(Platform::Mock(mock(Duration::from_millis(5))))?;
+
+        let mut recorder = ScreenRecorder::start(
+            &screen_cap,
+            &dir,
+            RecorderConfig {
+                mode: RecordMode::Rolling(span),
+                max_fps: None,
+                ..Default::default()
+            },
+        )?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+        std::thread::sleep(Duration::from_millis(200));
+
+        assert_eq!(recorder.flush_on_error(Ok::<_, &str>(1)), Ok(1));
+        assert!(!dir.join("flush-000").exists());
+
+        assert!(recorder.flush_on_error(Err::<(), _>("boom")).is_err());
+        let frames = read_recording(dir.join("flush-000"))?;
+        assert!(frames.len() >= 2);
+        // Only the tail of the 200ms is kept
+        assert!(frames.last().map_or(Duration::MAX, |f| f.at) <= span);
+        assert!(frames.iter().all(|f| f.path.exists()));
+
+        recorder.stop()?;
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_rolling_max_bytes() -> Result<()> {
+        let dir = recording_dir("rolling-bytes");
+        let (mut controller, screen_cap) =
+            controller(Platform::Mock(mock(Duration::from_millis(2))))?;
+
+        // Room for three 4x4 frames of a span that would hold many more
+        let mut recorder = ScreenRecorder::start(
+            &screen_cap,
+            &dir,
+            RecorderConfig {
+                mode: RecordMode::Rolling(Duration::from_secs(60)),
+                max_fps: None,
+                max_bytes: 3 * 4 * 4 * 4,
+            },
+        )?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+        std::thread::sleep(Duration::from_millis(100));
+
+        let flushed = recorder.flush()?.ok_or(anyhow!("nothing kept"))?;
+        assert_eq!(read_recording(flushed)?.len(), 3);
+
+        recorder.stop()?;
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_replaces_old_frames() -> Result<()> {
+        let dir = recording_dir("replace");
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("99999999.png"), b"stale")?;
+        std::fs::write(dir.join(ScreenRecorder::INDEX), "99999999 0 99999999.png\n")?;
+        std::fs::write(dir.join("notes.txt"), b"kept")?;
+
+        let (_controller, screen_cap) = controller(Platform::Mock(mock(Duration::from_millis(5))))?;
+        ScreenRecorder::start(&screen_cap, &dir, RecorderConfig::default())?.stop()?;
+
+        assert!(!dir.join("99999999.png").exists());
+        assert!(dir.join("notes.txt").exists());
+        assert!(read_recording(&dir)?.is_empty());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_failure_reported() -> Result<()> {
+        let dir = recording_dir("failure");
+        let (mut controller, screen_cap) =
+            controller(Platform::Mock(mock(Duration::from_millis(5))))?;
+
+        let recorder = ScreenRecorder::start(&screen_cap, &dir, RecorderConfig::default())?;
+        // Frames have nowhere to go once the directory is gone
+        std::fs::remove_dir_all(&dir)?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !recorder.failed() {
+            if Instant::now() > deadline {
+                return Err(anyhow!("write failure not reported"));
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(matches!(recorder.stop(), Err(RecorderError::Image(_) | RecorderError::Io(_))));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use crate::adb::{AdbConfig, AdbTouch};
+use crate::mock::MockConfig;
+use crate::mumu::MuMuConfig;
+use crate::replay::ReplayConfig;
+use crate::{Controller, ControllerError, DisplayTarget, Platform, ScreenCapture, controller};
+
+/// A backend address, `scheme://target?key=value&...`, e.g. `adb://127.0.0.1:5555` or
+/// `mumu://instance/1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackendUri {
+    /// Lowercase name the backend is registered under.
+    pub scheme: String,
+    pub target: String,
+    pub params: BTreeMap<String, String>,
+}
+
+impl BackendUri {
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params.get(key).map(String::as_str)
+    }
+
+    /// Reject parameters other than `known`, so a misspelled one is not silently ignored.
+    pub fn expect_params(&self, known: &[&str]) -> Result<(), ControllerError> {
+        match self.params.keys().find(|key| !known.contains(&key.as_str())) {
+            Some(_) => Err(ControllerError::InvalidUri(self.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for BackendUri {
+    type Err = ControllerError;
+
+    fn from_str(uri: &str) -> Result<Self, ControllerError> {
+        let (scheme, target) = uri
+            .trim()
+            .split_once("://")
+            .ok_or_else(|| ControllerError::InvalidUri(uri.to_string()))?;
+
+        if scheme.is_empty()
+            || !scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        {
+            return Err(ControllerError::InvalidUri(uri.to_string()));
+        }
+
+        let (target, query) = target.split_once('?').unwrap_or((target, ""));
+        let params = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+                _ => Err(ControllerError::InvalidUri(uri.to_string())),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(BackendUri {
+            scheme: scheme.to_ascii_lowercase(),
+            target: target.to_string(),
+            params,
+        })
+    }
+}
+
+impl Display for BackendUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.target)?;
+        for (i, (key, value)) in self.params.iter().enumerate() {
+            let separator = if i == 0 { '?' } else { '&' };
+            write!(f, "{separator}{key}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Connects to a backend given its address.
+pub type BackendFactory =
+    Arc<dyn Fn(&BackendUri) -> Result<(Controller, ScreenCapture), ControllerError> + Send + Sync>;
+
+/// Backends by scheme, so controllers can be created from config strings and crates
+/// outside this one can add their own.
+pub struct BackendRegistry {
+    factories: BTreeMap<String, BackendFactory>,
+}
+
+static GLOBAL: LazyLock<RwLock<BackendRegistry>> =
+    LazyLock::new(|| RwLock::new(BackendRegistry::new()));
+
+impl Default for BackendRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackendRegistry {
+    /// A registry with the built-in backends: `adb`, `mock`, `replay` and, on Windows, `mumu`.
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register("adb", |uri| controller(Platform::Adb(adb_config(uri)?)));
+        registry.register("mock", |uri| controller(Platform::Mock(mock_config(uri)?)));
+        registry.register("replay", |uri| {
+            controller(Platform::Replay(replay_config(uri)?))
+        });
+        #[cfg(windows)]
+        registry.register("mumu", |uri| {
+            controller(Platform::MuMu(mumu_config(uri, MuMuConfig::from_env()?)?))
+        });
+
+        registry
+    }
+
+    pub fn empty() -> Self {
+        BackendRegistry {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// The registry [`Platform::Uri`] resolves against. Its lock is only held to look up the
+    /// backend, not while connecting.
+    pub fn global() -> &'static RwLock<BackendRegistry> {
+        &GLOBAL
+    }
+
+    /// Add the backend for `scheme`, replacing any backend registered under it before.
+    pub fn register<F>(&mut self, scheme: &str, factory: F)
+    where
+        F: Fn(&BackendUri) -> Result<(Controller, ScreenCapture), ControllerError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.factories
+            .insert(scheme.to_ascii_lowercase(), Arc::new(factory));
+    }
+
+    pub fn unregister(&mut self, scheme: &str) -> bool {
+        self.factories
+            .remove(&scheme.to_ascii_lowercase())
+            .is_some()
+    }
+
+    pub fn schemes(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// Backend of `uri`, to connect with after letting go of the registry.
+    pub fn factory(&self, uri: &BackendUri) -> Result<BackendFactory, ControllerError> {
+        self.factories
+            .get(&uri.scheme)
+            .cloned()
+            .ok_or_else(|| ControllerError::UnknownBackend(uri.scheme.clone()))
+    }
+
+    pub fn connect(&self, uri: &str) -> Result<(Controller, ScreenCapture), ControllerError> {
+        let uri: BackendUri = uri.parse()?;
+        self.factory(&uri)?(&uri)
+    }
+}
+
+/// `adb://` uses the only device, `adb://<serial>` a given one. `touch=minitouch` drives
+/// touches through minitouch on `socket`, `minitouch` by default, and `server` is the adb
+/// server address.
+fn adb_config(uri: &BackendUri) -> Result<AdbConfig, ControllerError> {
+    uri.expect_params(&["touch", "socket", "server"])?;
+    let invalid = || ControllerError::InvalidUri(uri.to_string());
+
+    let socket = uri.param("socket");
+    let touch = match (uri.param("touch"), socket) {
+        (None | Some("input"), None) => AdbTouch::Input,
+        (Some("minitouch"), socket) => AdbTouch::Minitouch {
+            socket: socket.unwrap_or("minitouch").to_string(),
+        },
+        _ => return Err(invalid()),
+    };
+
+    let mut config = AdbConfig {
+        serial: (!uri.target.is_empty()).then(|| uri.target.clone()),
+        touch,
+        ..Default::default()
+    };
+    if let Some(server) = uri.param("server") {
+        config.server = server.to_string();
+    }
+    Ok(config)
+}
+
+/// `mock://<dir>` serves the pngs in `dir`.
+fn mock_config(uri: &BackendUri) -> Result<MockConfig, ControllerError> {
+    uri.expect_params(&[])?;
+    if uri.target.is_empty() {
+        return Err(ControllerError::InvalidUri(uri.to_string()));
+    }
+    Ok(MockConfig::from_dir(&uri.target))
+}
+
+/// `replay://<dir>` plays back the session recorded in `dir`.
+fn replay_config(uri: &BackendUri) -> Result<ReplayConfig, ControllerError> {
+    uri.expect_params(&[])?;
+    if uri.target.is_empty() {
+        return Err(ControllerError::InvalidUri(uri.to_string()));
+    }
+    Ok(ReplayConfig::new(&uri.target))
+}
+
+/// `mumu://` takes `instance/<n>`, `display/<id>` and `package/<name>` pairs on top of `base`,
+/// e.g. `mumu://instance/1/package/com.example.game`. `app_index=<n>` picks the clone of the
+/// package, e.g. `mumu://package/com.example.game?app_index=1`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn mumu_config(uri: &BackendUri, mut base: MuMuConfig) -> Result<MuMuConfig, ControllerError> {
+    uri.expect_params(&["app_index"])?;
+    let invalid = || ControllerError::InvalidUri(uri.to_string());
+
+    let mut segments = uri.target.split('/').filter(|s| !s.is_empty());
+    while let Some(key) = segments.next() {
+        let value = segments.next().ok_or_else(invalid)?;
+        match key {
+            "instance" => base.instance = value.parse().map_err(|_| invalid())?,
+            "display" => base.display = DisplayTarget::Id(value.parse().map_err(|_| invalid())?),
+            "package" => {
+                base.display = DisplayTarget::Package {
+                    name: value.to_string(),
+                    app_index: 0,
+                }
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    if let Some(index) = uri.param("app_index") {
+        let DisplayTarget::Package { app_index, .. } = &mut base.display else {
+            return Err(invalid());
+        };
+        *app_index = index.parse().map_err(|_| invalid())?;
+    }
+
+    Ok(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Result, anyhow};
+    use image::{Rgba, RgbaImage};
+
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::mock::MockController;
+    use crate::{Command, ControllerTrait};
+
+    #[test]
+    fn test_parse_uri() -> Result<()> {
+        let uri: BackendUri = "ADB://127.0.0.1:5555".parse()?;
+        assert_eq!(uri.scheme, "adb");
+        assert_eq!(uri.target, "127.0.0.1:5555");
+        assert_eq!(uri.to_string(), "adb://127.0.0.1:5555");
+        assert!(uri.params.is_empty());
+
+        let uri: BackendUri = "adb://emulator-5554?touch=minitouch&socket=mt".parse()?;
+        assert_eq!(uri.target, "emulator-5554");
+        assert_eq!(uri.param("touch"), Some("minitouch"));
+        assert_eq!(uri.param("socket"), Some("mt"));
+        assert_eq!(
+            uri.to_string(),
+            "adb://emulator-5554?socket=mt&touch=minitouch"
+        );
+
+        for invalid in ["127.0.0.1:5555", "://x", "a b://x", "adb://x?touch", "adb://x?=1"] {
+            assert!(matches!(
+                invalid.parse::<BackendUri>(),
+                Err(ControllerError::InvalidUri(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_builtin_configs() -> Result<()> {
+        let adb = adb_config(&"adb://".parse()?)?;
+        assert_eq!((adb.serial, adb.touch), (None, AdbTouch::Input));
+        assert_eq!(
+            adb_config(&"adb://emulator-5554".parse()?)?.serial.as_deref(),
+            Some("emulator-5554")
+        );
+        assert_eq!(
+            adb_config(&"adb://emulator-5554?touch=minitouch".parse()?)?.touch,
+            AdbTouch::Minitouch {
+                socket: "minitouch".to_string()
+            }
+        );
+        assert_eq!(
+            adb_config(&"adb://?touch=minitouch&socket=mt".parse()?)?.touch,
+            AdbTouch::Minitouch {
+                socket: "mt".to_string()
+            }
+        );
+        assert_eq!(
+            adb_config(&"adb://?server=127.0.0.1:5038".parse()?)?.server,
+            "127.0.0.1:5038"
+        );
+        for invalid in ["adb://?touch=hid", "adb://?socket=mt", "adb://?serial=x"] {
+            assert!(adb_config(&invalid.parse()?).is_err());
+        }
+
+        let config = mumu_config(
+            &"mumu://instance/1/package/com.example".parse()?,
+            MuMuConfig::default(),
+        )?;
+        assert_eq!(config.instance, 1);
+        assert_eq!(
+            config.display,
+            DisplayTarget::Package {
+                name: "com.example".to_string(),
+                app_index: 0
+            }
+        );
+
+        let config = mumu_config(
+            &"mumu://package/com.example?app_index=2".parse()?,
+            MuMuConfig::default(),
+        )?;
+        assert_eq!(
+            config.display,
+            DisplayTarget::Package {
+                name: "com.example".to_string(),
+                app_index: 2
+            }
+        );
+
+        for invalid in [
+            "mumu://instance",
+            "mumu://instance/x",
+            "mumu://color/red",
+            "mumu://instance/1?app_index=1",
+            "mumu://package/com.example?app_index=x",
+            "mumu://package/com.example?clone=1",
+        ] {
+            assert!(mumu_config(&invalid.parse()?, MuMuConfig::default()).is_err());
+        }
+        assert!(mock_config(&"mock://".parse()?).is_err());
+        assert!(mock_config(&"mock://frames?fps=30".parse()?).is_err());
+        assert!(replay_config(&"replay://".parse()?).is_err());
+        assert_eq!(
+            replay_config(&"replay://runs/1".parse()?)?.dir,
+            PathBuf::from("runs/1")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_backend() -> Result<()> {
+        let mut registry = BackendRegistry::new();
+        assert!(matches!(
+            registry.connect("custom://x"),
+            Err(ControllerError::UnknownBackend(scheme)) if scheme == "custom"
+        ));
+
+        registry.register("custom", |_| {
+            let config =
+                MockConfig::from_images(vec![RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]))]);
+            let (backend, screen_capture) = MockController::new(config)?;
+            Ok((Controller::Backend(Box::new(backend)), screen_capture))
+        });
+        assert!(registry.schemes().any(|scheme| scheme == "custom"));
+
+        let (mut controller, _screen_cap) = registry.connect("custom://x")?;
+        let Controller::Backend(_) = &controller else {
+            return Err(anyhow!("not a boxed backend"));
+        };
+        controller.execute(Command::Tab { x: 0, y: 0 })?;
+
+        assert!(registry.unregister("CUSTOM"));
+        assert!(registry.connect("custom://x").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_connect_outside_lock() -> Result<()> {
+        // A backend that registers another while connecting, it would deadlock if the
+        // registry stayed locked
+        BackendRegistry::global()
+            .write()
+            .unwrap()
+            .register("lock-test", |_| {
+                BackendRegistry::global()
+                    .write()
+                    .unwrap()
+                    .register("lock-test-inner", |_| Err(ControllerError::Closed));
+                let config =
+                    MockConfig::from_images(vec![RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]))]);
+                let (backend, screen_capture) = MockController::new(config)?;
+                Ok((Controller::Backend(Box::new(backend)), screen_capture))
+            });
+
+        controller(Platform::Uri("lock-test://x".to_string()))?;
+        assert!(
+            BackendRegistry::global()
+                .read()
+                .unwrap()
+                .schemes()
+                .any(|scheme| scheme == "lock-test-inner")
+        );
+
+        Ok(())
+    }
+}
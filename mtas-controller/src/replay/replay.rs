@@ -0,0 +1,397 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc::SendError,
+    },
+    thread::sleep,
+    time::Duration,
+};
+
+use crate::{
+    CaptureHandle, Command, ControllerTrait, ErrorClass, Frame, FrameSource, Journal, JournalEntry,
+    JournalError, Return, ScreenCapCommand, ScreenCapture, read_journal, spawn_capture,
+};
+use image::RgbaImage;
+use thiserror::Error;
+
+use tracing::*;
+
+/// A session recorded with [`crate::RecordingController`] to play back.
+#[derive(Clone, Debug)]
+pub struct ReplayConfig {
+    pub dir: PathBuf,
+    /// Simulated time one capture takes.
+    pub frame_interval: Duration,
+}
+
+impl ReplayConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ReplayConfig {
+            dir: dir.into(),
+            frame_interval: Duration::from_millis(16),
+        }
+    }
+}
+
+/// Plays a recorded session back: serves the recorded frames, checks that every command
+/// matches the recorded one and returns what was returned back then.
+pub struct ReplayController {
+    capture: CaptureHandle,
+    entries: Vec<JournalEntry>,
+    next: usize,
+    /// Recorded `seq` of the frame the next command was sent on.
+    frame: Arc<AtomicU64>,
+}
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("Failed to read session: {0}")]
+    Journal(#[from] JournalError),
+
+    #[error("Failed to decode session frame: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Command {index} Differs From The Session, expected {expected:?}, got {got:?}")]
+    Mismatch {
+        index: usize,
+        expected: Box<Command>,
+        got: Box<Command>,
+    },
+
+    #[error("Session Has Ended, Got {0:?}")]
+    Exhausted(Box<Command>),
+
+    /// Sessions without a recorded class replay their errors as [`ErrorClass::Fatal`].
+    #[error("Recorded Error: {message}")]
+    Recorded { message: String, class: ErrorClass },
+
+    #[error("Screen Capture Command Failed to Send：{0}")]
+    ScreenCap(#[from] SendError<ScreenCapCommand>),
+}
+
+impl ReplayError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            ReplayError::Journal(_) | ReplayError::Image(_) | ReplayError::Exhausted(_) => {
+                ErrorClass::Fatal
+            }
+            ReplayError::Recorded { class, .. } => *class,
+            ReplayError::Mismatch { .. } => ErrorClass::InvalidArgument,
+            ReplayError::ScreenCap(_) => ErrorClass::NeedsReconnect,
+        }
+    }
+
+    /// Whether the command never reached the session, see
+    /// [`crate::ControllerError::is_unsent`].
+    pub fn is_unsent(&self) -> bool {
+        matches!(self, ReplayError::ScreenCap(_))
+    }
+}
+
+struct ReplayFrameSource {
+    /// By recorded `seq`.
+    frames: BTreeMap<u64, RgbaImage>,
+    frame: Arc<AtomicU64>,
+    frame_interval: Duration,
+}
+
+impl FrameSource for ReplayFrameSource {
+    type Error = ReplayError;
+
+    fn capture(&mut self, frame: &mut Frame) -> Result<(), ReplayError> {
+        sleep(self.frame_interval);
+
+        // The newest frame recorded up to the wanted one, commands sent before the first
+        // frame see the first
+        let seq = self.frame.load(Ordering::Relaxed);
+        let image = self
+            .frames
+            .range(..=seq)
+            .next_back()
+            .or_else(|| self.frames.first_key_value())
+            .map(|(_, image)| image)
+            .expect("replay frames are never empty");
+
+        frame.resize(image.width() as usize, image.height() as usize);
+        frame.data.copy_from_slice(image.as_raw());
+
+        Ok(())
+    }
+}
+
+fn load_frames(config: &ReplayConfig) -> Result<BTreeMap<u64, RgbaImage>, ReplayError> {
+    let mut frames = BTreeMap::new();
+
+    let dir = config.dir.join(Journal::FRAMES);
+    if dir.is_dir() {
+        for entry in std::fs::read_dir(&dir).map_err(JournalError::from)? {
+            let path = entry.map_err(JournalError::from)?.path();
+            let Some(seq) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok())
+            else {
+                continue;
+            };
+            frames.insert(seq, image::open(&path)?.to_rgba8());
+        }
+    }
+
+    // A session without frames still replays its commands
+    if frames.is_empty() {
+        frames.insert(0, RgbaImage::new(1, 1));
+    }
+
+    Ok(frames)
+}
+
+impl ControllerTrait for ReplayController {
+    type Config = ReplayConfig;
+    type Error = ReplayError;
+
+    fn new(config: ReplayConfig) -> Result<(Self, ScreenCapture), ReplayError> {
+        let entries = read_journal(&config.dir)?;
+        let frames = load_frames(&config)?;
+
+        info!(
+            "Replaying {} commands and {} frames from {:?}",
+            entries.len(),
+            frames.len(),
+            config.dir
+        );
+
+        let (width, height) = frames
+            .first_key_value()
+            .map(|(_, image)| image.dimensions())
+            .unwrap_or_default();
+        let frame = Arc::new(AtomicU64::new(entries.first().map_or(0, |e| e.frame)));
+
+        let (capture, screen_capture) = spawn_capture(
+            ReplayFrameSource {
+                frames,
+                frame: frame.clone(),
+                frame_interval: config.frame_interval,
+            },
+            width as usize,
+            height as usize,
+        );
+
+        Ok((
+            ReplayController {
+                capture,
+                entries,
+                next: 0,
+                frame,
+            },
+            screen_capture,
+        ))
+    }
+
+    #[instrument(skip_all)]
+    fn execute(&mut self, command: Command) -> Result<Return, ReplayError> {
+        let Some(entry) = self.entries.get(self.next) else {
+            return Err(ReplayError::Exhausted(Box::new(command)));
+        };
+
+        // Compared as json, the form they were recorded in
+        if serde_json::to_value(&command).ok() != serde_json::to_value(&entry.command).ok() {
+            return Err(ReplayError::Mismatch {
+                index: self.next,
+                expected: Box::new(entry.command.clone()),
+                got: Box::new(command),
+            });
+        }
+        let result = entry.result.clone();
+        let class = entry.error_class.unwrap_or(ErrorClass::Fatal);
+        self.next += 1;
+
+        // Capture still follows the commands, so the recorded frames keep flowing
+        match command {
+            Command::ControlScreenCapture { start } => {
+                self.capture.control_screen_capture(start)?;
+            }
+            Command::SetFrameRate(rate) => {
+                self.capture.set_frame_rate(rate)?;
+            }
+            _ => {}
+        }
+
+        if let Some(next) = self.entries.get(self.next) {
+            self.frame.store(next.frame, Ordering::Relaxed);
+        }
+
+        result.map_err(|message| ReplayError::Recorded { message, class })
+    }
+}
+
+impl ReplayController {
+    /// Commands of the session not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.entries.len() - self.next
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use anyhow::{Result, anyhow};
+    use image::Rgba;
+
+    use super::*;
+    use crate::mock::MockConfig;
+    use crate::{
+        Controller, ControllerBackend, ControllerError, DisplayTarget, Platform,
+        RecordingController, RetryPolicy, controller,
+    };
+
+    fn session_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mtas-replay-{}-{}", name, std::process::id()))
+    }
+
+    fn wait_frame(screen_cap: &mut ScreenCapture) -> Result<u64> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !screen_cap.update() {
+            if Instant::now() > deadline {
+                return Err(anyhow!("no frame published"));
+            }
+            sleep(Duration::from_millis(1));
+        }
+        Ok(screen_cap.read().meta.seq)
+    }
+
+    fn script() -> Vec<Command> {
+        vec![
+            Command::Tab { x: 1, y: 2 },
+            Command::Text("hello".to_string()),
+            Command::BindDisplay(DisplayTarget::Id(2)),
+            Command::TouchUp { contact: 5 },
+        ]
+    }
+
+    fn record(dir: &PathBuf) -> Result<()> {
+        let mut config =
+            MockConfig::from_images(vec![RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]))]);
+        config.frame_interval = Duration::from_millis(1);
+
+        let (inner, mut screen_cap) = controller(Platform::Mock(config))?;
+        let mut recorder = RecordingController::new(inner, &screen_cap, Journal::create(dir)?);
+
+        recorder.execute(Command::ControlScreenCapture { start: true })?;
+        wait_frame(&mut screen_cap)?;
+        for command in script() {
+            let _ = recorder.execute(command);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_and_replay() -> Result<()> {
+        let dir = session_dir("session");
+        record(&dir)?;
+
+        let entries = read_journal(&dir)?;
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0].frame, 0);
+        assert!(entries[1].frame > 0);
+        assert!(Journal::frame_path(&dir, entries[1].frame).exists());
+        assert!(matches!(entries[3].result, Ok(Return::DisplayId(2))));
+        assert!(entries[4].result.is_err());
+
+        let (mut controller, mut screen_cap) = controller(Platform::Replay(ReplayConfig {
+            dir: dir.clone(),
+            frame_interval: Duration::from_millis(1),
+        }))?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+        wait_frame(&mut screen_cap)?;
+        assert_eq!(screen_cap.read().data[..4], [10, 20, 30, 255]);
+
+        controller.execute(Command::Tab { x: 1, y: 2 })?;
+        controller.execute(Command::Text("hello".to_string()))?;
+        assert!(matches!(
+            controller.execute(Command::BindDisplay(DisplayTarget::Id(2)))?,
+            Return::DisplayId(2)
+        ));
+        assert!(matches!(
+            controller.execute(Command::TouchUp { contact: 5 }),
+            Err(ControllerError::ReplayError(ReplayError::Recorded {
+                class: ErrorClass::InvalidArgument,
+                ..
+            }))
+        ));
+
+        let Controller::Replay(replay) = &controller else {
+            return Err(anyhow!("not a replay controller"));
+        };
+        assert_eq!(replay.remaining(), 0);
+        assert!(matches!(
+            controller.execute(Command::Tab { x: 1, y: 2 }),
+            Err(ControllerError::ReplayError(ReplayError::Exhausted(_)))
+        ));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_mismatch() -> Result<()> {
+        let dir = session_dir("mismatch");
+        record(&dir)?;
+
+        let (mut controller, _screen_cap) = controller(Platform::Replay(ReplayConfig::new(&dir)))?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+
+        assert!(matches!(
+            controller.execute(Command::Tab { x: 1, y: 3 }),
+            Err(ControllerError::ReplayError(ReplayError::Mismatch {
+                index: 1,
+                ..
+            }))
+        ));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_retries() -> Result<()> {
+        let dir = session_dir("retries");
+        let policy = RetryPolicy {
+            initial: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let config =
+            MockConfig::from_images(vec![RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]))]);
+        let (inner, screen_cap) = controller(Platform::Mock(config.clone()))?;
+        let mut recorder = RecordingController::new(inner, &screen_cap, Journal::create(&dir)?);
+        config.link.busy(1);
+        policy.run(|| recorder.execute(Command::Tab { x: 1, y: 2 }))?;
+        drop(recorder);
+
+        let entries = read_journal(&dir)?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].error_class, Some(ErrorClass::Transient));
+        assert_eq!(entries[1].error_class, None);
+
+        // The recorded failure is retried like it was back then
+        let (mut controller, _screen_cap) = controller(Platform::Replay(ReplayConfig::new(&dir)))?;
+        controller.execute_with(Command::Tab { x: 1, y: 2 }, &policy)?;
+
+        let Controller::Replay(replay) = &controller else {
+            return Err(anyhow!("not a replay controller"));
+        };
+        assert_eq!(replay.remaining(), 0);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::*;
+
+use crate::{Command, Controller, ControllerError, Return};
+
+/// What can be done about an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorClass {
+    /// Likely to go away on its own, worth trying again. The command was not carried out,
+    /// so retrying is safe even for ones like `Tab` and `Text` that must not run twice.
+    Transient,
+    /// The connection is gone, only a new controller will work, see [`crate::Supervisor`].
+    NeedsReconnect,
+    /// Setup or environment problem, trying again won't help.
+    Fatal,
+    /// The command itself is wrong.
+    InvalidArgument,
+}
+
+impl ErrorClass {
+    pub fn is_retryable(self) -> bool {
+        self == ErrorClass::Transient
+    }
+}
+
+/// How to retry an operation that failed with a [`ErrorClass::Transient`] error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Tries in total, the first one included.
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for every later one.
+    pub initial: Duration,
+    pub max: Duration,
+    /// Every wait is scaled by a random factor within `1 ± jitter`.
+    pub jitter: f64,
+    /// No retry starts later than this after the first try.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            initial: Duration::from_millis(50),
+            max: Duration::from_secs(1),
+            jitter: 0.2,
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Try once, never retry.
+    pub const NONE: RetryPolicy = RetryPolicy {
+        attempts: 1,
+        initial: Duration::ZERO,
+        max: Duration::ZERO,
+        jitter: 0.0,
+        deadline: None,
+    };
+
+    /// Wait before retry `retry`, counting from 1, without jitter.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+
+    fn jittered(&self, retry: u32) -> Duration {
+        let delay = self.delay(retry);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+
+        // A fresh RandomState is seeded differently every time, good enough for spreading retries
+        let random = RandomState::new().hash_one(retry) as f64 / u64::MAX as f64;
+        delay.mul_f64((1.0 + self.jitter * (2.0 * random - 1.0)).max(0.0))
+    }
+
+    /// Run `op` until it succeeds, fails with an error that is not transient, runs out of
+    /// attempts or would retry past the deadline. Returns the last error.
+    pub fn run<T>(
+        &self,
+        mut op: impl FnMut() -> Result<T, ControllerError>,
+    ) -> Result<T, ControllerError> {
+        let start = Instant::now();
+        let mut attempt = 1;
+
+        loop {
+            let error = match op() {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            if !error.class().is_retryable() || attempt >= self.attempts {
+                return Err(error);
+            }
+
+            let wait = self.jittered(attempt);
+            if self
+                .deadline
+                .is_some_and(|deadline| start.elapsed() + wait > deadline)
+            {
+                return Err(error);
+            }
+
+            warn!(
+                "Attempt {} failed, retrying in {:?}: {}",
+                attempt, wait, error
+            );
+            sleep(wait);
+            attempt += 1;
+        }
+    }
+}
+
+impl Controller {
+    /// [`Controller::execute`] with transient errors retried by `policy`.
+    pub fn execute_with(
+        &mut self,
+        command: Command,
+        policy: &RetryPolicy,
+    ) -> Result<Return, ControllerError> {
+        policy.run(|| self.execute(command.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::mock::{MockConfig, MockError};
+    use crate::{Platform, controller};
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 4,
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(4),
+            jitter: 0.5,
+            deadline: None,
+        }
+    }
+
+    fn mock() -> MockConfig {
+        MockConfig::from_images(vec![RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]))])
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..fast()
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(1));
+        assert_eq!(policy.delay(2), Duration::from_millis(2));
+        assert_eq!(policy.delay(40), Duration::from_millis(4));
+
+        let policy = RetryPolicy {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            ..fast()
+        };
+        for _ in 0..20 {
+            let wait = policy.jittered(1);
+            assert!(wait >= Duration::from_millis(50) && wait <= Duration::from_millis(150));
+        }
+    }
+
+    #[test]
+    fn test_error_class() {
+        let class = |e: MockError| ControllerError::from(e).class();
+        assert_eq!(class(MockError::Busy), ErrorClass::Transient);
+        assert_eq!(class(MockError::Disconnected), ErrorClass::NeedsReconnect);
+        assert_eq!(class(MockError::NoFrames), ErrorClass::Fatal);
+        assert_eq!(
+            ControllerError::InvalidUri("x".to_string()).class(),
+            ErrorClass::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_retry_transient() -> Result<()> {
+        let config = mock();
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config.clone()))?;
+
+        config.link.busy(2);
+        controller.execute_with(Command::Tab { x: 1, y: 2 }, &fast())?;
+        assert_eq!(config.log.commands().len(), 3);
+
+        config.link.busy(10);
+        assert!(matches!(
+            controller.execute_with(Command::Tab { x: 1, y: 2 }, &fast()),
+            Err(ControllerError::MockError(MockError::Busy))
+        ));
+        assert_eq!(config.log.commands().len(), 7);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_stops_early() -> Result<()> {
+        let config = mock();
+        let (mut controller, _screen_cap) = controller(Platform::Mock(config.clone()))?;
+
+        // Not transient, tried once
+        config.link.drop_connections();
+        assert!(
+            controller
+                .execute_with(Command::Tab { x: 1, y: 2 }, &fast())
+                .is_err()
+        );
+        assert_eq!(config.log.commands().len(), 1);
+
+        // The next wait would pass the deadline
+        let (mut controller, _screen_cap) = crate::controller(Platform::Mock(config.clone()))?;
+        config.link.busy(10);
+        let policy = RetryPolicy {
+            initial: Duration::from_millis(50),
+            max: Duration::from_millis(50),
+            jitter: 0.0,
+            deadline: Some(Duration::from_millis(20)),
+            ..fast()
+        };
+        let start = Instant::now();
+        assert!(
+            controller
+                .execute_with(Command::Tab { x: 1, y: 2 }, &policy)
+                .is_err()
+        );
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        Ok(())
+    }
+}
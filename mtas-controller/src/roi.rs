@@ -0,0 +1,324 @@
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::{
+    CaptureShared, Frame, FrameRate, FrameSubscriber, Region, ScreenCapture, SubscriberConfig,
+};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RoiError {
+    #[error("Region of Interest is Empty")]
+    Empty,
+
+    #[error("Region of Interest {region:?} is Outside the {}x{} Display", display.0, display.1)]
+    OutOfBounds {
+        region: Region,
+        display: (usize, usize),
+    },
+}
+
+/// Regions leased on a [`ScreenCapture`], kept with the capture thread.
+#[derive(Debug, Default)]
+pub(crate) struct RoiState {
+    leases: BTreeMap<u64, Region>,
+    next: u64,
+    /// Capture only the leased regions on backends that can.
+    roi_only: bool,
+    /// Rate of ROI only captures, the capture rate when unset.
+    pub(crate) rate: Option<FrameRate>,
+    /// Display size leases are checked against, updated by the capture thread.
+    pub(crate) display: (usize, usize),
+}
+
+impl RoiState {
+    /// Longest an ROI only capture goes without a full frame for the other consumers.
+    pub(crate) const FULL_INTERVAL: Duration = Duration::from_millis(500);
+
+    pub(crate) fn new(display: (usize, usize)) -> Self {
+        RoiState {
+            display,
+            ..Default::default()
+        }
+    }
+
+    fn union(&self) -> Option<Region> {
+        self.leases
+            .values()
+            .copied()
+            .reduce(|union, region| union.union(&region))
+    }
+
+    /// Region the capture thread should grab instead of the `display` sized screen, if any.
+    pub(crate) fn capture_region(&self, display: (usize, usize)) -> Option<Region> {
+        if !self.roi_only {
+            return None;
+        }
+        self.union()
+            .map(|union| union.clamp(display.0, display.1))
+            .filter(|region| !region.is_empty())
+    }
+}
+
+/// A region of interest of a [`ScreenCapture`], handing out crops of the frames that cover
+/// it. The region stops counting towards the captured area once the lease is dropped.
+pub struct RoiLease {
+    id: u64,
+    region: Region,
+    shared: Arc<CaptureShared>,
+    subscriber: FrameSubscriber,
+}
+
+impl RoiLease {
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Newest frame cropped to the region, `None` before any frame covered it.
+    pub fn latest(&self) -> Option<Frame> {
+        self.subscriber.latest()?.crop(self.region)
+    }
+
+    /// A crop of the newest frame if it was not handed out yet and covers the region.
+    pub fn try_next(&mut self) -> Option<Frame> {
+        self.subscriber.try_next()?.crop(self.region)
+    }
+
+    /// Block until a new frame covering the region arrives, `None` on timeout.
+    pub fn next(&mut self, timeout: Duration) -> Option<Frame> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let frame = self.subscriber.next(remaining)?;
+            if let Some(crop) = frame.crop(self.region) {
+                return Some(crop);
+            }
+        }
+    }
+
+    pub fn set_max_fps(&self, max_fps: Option<f64>) {
+        self.subscriber.set_max_fps(max_fps);
+    }
+}
+
+impl Drop for RoiLease {
+    fn drop(&mut self) {
+        self.shared.rois.lock().unwrap().leases.remove(&self.id);
+    }
+}
+
+impl ScreenCapture {
+    /// Lease `region` of the display, in display pixels. Fails if it is empty or reaches out
+    /// of the display.
+    pub fn lease_roi(&self, region: Region) -> Result<RoiLease, RoiError> {
+        let mut rois = self.shared.rois.lock().unwrap();
+        if region.is_empty() {
+            return Err(RoiError::Empty);
+        }
+        let display = rois.display;
+        if region.clamp(display.0, display.1) != region {
+            return Err(RoiError::OutOfBounds { region, display });
+        }
+
+        let id = rois.next;
+        rois.next += 1;
+        rois.leases.insert(id, region);
+
+        Ok(RoiLease {
+            id,
+            region,
+            shared: self.shared.clone(),
+            subscriber: self.shared.roi_hub.subscribe(SubscriberConfig::default()),
+        })
+    }
+
+    /// Smallest region covering every lease, what an ROI only capture grabs.
+    pub fn roi_union(&self) -> Option<Region> {
+        self.shared.rois.lock().unwrap().union()
+    }
+
+    /// Capture only [`Self::roi_union`] while there are leases, on backends that can.
+    ///
+    /// The partial frames only reach leases. [`Self::read`] and subscribers keep getting full
+    /// frames, but only one every half second, so they drop to about 2 fps meanwhile.
+    pub fn set_roi_only(&self, on: bool) {
+        self.shared.rois.lock().unwrap().roi_only = on;
+    }
+
+    /// Rate of the partial frames of an ROI only capture, `None` to follow
+    /// [`crate::Command::SetFrameRate`]. Partial frames never wait for a read, an adaptive
+    /// rate only caps them.
+    pub fn set_roi_rate(&self, rate: Option<FrameRate>) {
+        self.shared.rois.lock().unwrap().rate = rate;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Result, anyhow};
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::mock::MockConfig;
+    use crate::{
+        Command, FrameRate, FrameSource, Platform, SharedFrame, controller, spawn_capture,
+    };
+
+    /// First frame passing `pred`.
+    fn wait_frame(
+        subscriber: &mut FrameSubscriber,
+        pred: impl Fn(&Frame) -> bool,
+    ) -> Result<SharedFrame> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let frame = subscriber
+                .next(remaining)
+                .ok_or(anyhow!("no matching frame"))?;
+            if pred(&frame) {
+                return Ok(frame);
+            }
+        }
+    }
+
+    #[test]
+    fn test_roi_native() -> Result<()> {
+        let image = RgbaImage::from_fn(64, 64, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+        let mut config = MockConfig::from_images(vec![image]);
+        config.frame_interval = Duration::from_millis(8);
+        let log = config.log.clone();
+        let (mut controller, mut screen_cap) = controller(Platform::Mock(config))?;
+        controller.execute(Command::SetFrameRate(FrameRate::Unlimited))?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+
+        let mut rois = screen_cap
+            .shared
+            .roi_hub
+            .subscribe(SubscriberConfig::default());
+        let mut button = screen_cap.lease_roi(Region::new(10, 10, 8, 8))?;
+        let counter = screen_cap.lease_roi(Region::new(30, 5, 4, 4))?;
+        let union = Region::new(10, 5, 24, 13);
+        assert_eq!(screen_cap.roi_union(), Some(union));
+
+        screen_cap.set_roi_only(true);
+        let frame = wait_frame(&mut rois, |f| f.meta.region == union)?;
+        assert!(frame.is_partial());
+        assert_eq!((frame.width(), frame.height()), (24, 13));
+        assert_eq!(frame.meta.display, (64, 64));
+        assert!(log.region_captures() > 0);
+
+        let crop = button
+            .next(Duration::from_secs(2))
+            .ok_or(anyhow!("no crop"))?;
+        assert_eq!((crop.width(), crop.height()), (8, 8));
+        assert_eq!(crop.data[..4], [10, 10, 0, 255]);
+        assert_eq!(crop.data[crop.data.len() - 4..], [17, 17, 0, 255]);
+
+        // Everyone else still gets whole frames
+        let seen = screen_cap.read().meta.seq;
+        let frame = screen_cap
+            .wait_new_frame(Duration::from_secs(2))
+            .ok_or(anyhow!("no full frame"))?;
+        assert!(frame.meta.seq > seen && !frame.is_partial());
+        assert_eq!((frame.width(), frame.height()), (64, 64));
+        assert!(screen_cap.hub().latest().is_some_and(|f| !f.is_partial()));
+
+        // The union shrinks as leases go away and full frames come back with the last
+        drop(counter);
+        assert_eq!(screen_cap.roi_union(), Some(button.region()));
+        wait_frame(&mut rois, |f| f.meta.region == button.region())?;
+
+        drop(button);
+        assert_eq!(screen_cap.roi_union(), None);
+        let captures = log.region_captures();
+        let frame = wait_frame(&mut rois, |f| !f.is_partial())?;
+        assert_eq!((frame.width(), frame.height()), (64, 64));
+        wait_frame(&mut rois, |f| !f.is_partial())?;
+        assert!(log.region_captures() <= captures + 1);
+
+        Ok(())
+    }
+
+    struct Gray;
+
+    impl FrameSource for Gray {
+        type Error = String;
+
+        fn capture(&mut self, frame: &mut Frame) -> Result<(), String> {
+            std::thread::sleep(Duration::from_millis(1));
+            frame.resize(4, 4);
+            frame.data.fill(128);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_roi_fallback() -> Result<()> {
+        let (handle, screen_cap) = spawn_capture(Gray, 4, 4);
+        let mut lease = screen_cap.lease_roi(Region::new(1, 1, 2, 2))?;
+        screen_cap.set_roi_only(true);
+        handle.control_screen_capture(true)?;
+
+        // Sources without region support keep capturing full frames
+        let crop = lease
+            .next(Duration::from_secs(2))
+            .ok_or(anyhow!("no crop"))?;
+        assert_eq!((crop.width(), crop.height()), (2, 2));
+        assert_eq!(crop.meta.region, lease.region());
+
+        // Regions reaching out of the display are refused
+        assert_eq!(
+            screen_cap.lease_roi(Region::new(3, 3, 2, 2)).err(),
+            Some(RoiError::OutOfBounds {
+                region: Region::new(3, 3, 2, 2),
+                display: (4, 4),
+            })
+        );
+        assert_eq!(
+            screen_cap.lease_roi(Region::new(1, 1, 0, 2)).err(),
+            Some(RoiError::Empty)
+        );
+        assert_eq!(screen_cap.roi_union(), Some(lease.region()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_roi_rate() -> Result<()> {
+        let image = RgbaImage::from_pixel(64, 64, Rgba([0, 0, 0, 255]));
+        let (mut controller, screen_cap) =
+            controller(Platform::Mock(MockConfig::from_images(vec![image])))?;
+        controller.execute(Command::SetFrameRate(FrameRate::Fixed(10.0)))?;
+        controller.execute(Command::ControlScreenCapture { start: true })?;
+
+        let mut frames = screen_cap.hub().subscribe(SubscriberConfig::default());
+        let mut lease = screen_cap.lease_roi(Region::new(8, 8, 4, 4))?;
+        screen_cap.set_roi_rate(Some(FrameRate::Fixed(200.0)));
+        screen_cap.set_roi_only(true);
+        lease
+            .next(Duration::from_secs(2))
+            .ok_or(anyhow!("no crop"))?;
+
+        // Partial frames outpace the 10 fps capture rate, full frames drop to the
+        // fallback interval
+        let window = Duration::from_millis(1200);
+        let (mut crops, mut full) = (0, 0);
+        let deadline = Instant::now() + window;
+        while Instant::now() < deadline {
+            if lease.next(Duration::from_millis(20)).is_some() {
+                crops += 1;
+            }
+            while frames.try_next().is_some() {
+                full += 1;
+            }
+        }
+        assert!(crops > 24, "{crops} crops");
+        assert!((1..=4).contains(&full), "{full} full frames");
+
+        Ok(())
+    }
+}
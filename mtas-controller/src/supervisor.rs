@@ -0,0 +1,440 @@
+use std::{
+    mem::discriminant,
+    sync::mpsc::{Receiver, Sender, TryIter, channel},
+    thread::sleep,
+    time::Duration,
+};
+
+use tracing::*;
+
+use crate::{
+    CaptureEvent, Command, Controller, ControllerError, Platform, Return, ScreenCapture, controller,
+};
+
+/// Connection changes of a [`Supervisor`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// A new controller and capture are up, subscribers of the old capture should resubscribe.
+    Connected,
+    /// The connection was found dead, `error` is what gave it away.
+    Disconnected { error: String },
+    /// Connect attempt `attempt` is made after waiting `retry_in`.
+    Reconnecting { attempt: u32, retry_in: Duration },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Wait before the second attempt, doubled for every later one.
+    pub initial: Duration,
+    pub max: Duration,
+    /// Give up after this many failed attempts, `None` keeps trying.
+    pub max_attempts: Option<u32>,
+    /// Consecutive capture failures that count as a dead connection.
+    pub capture_failures: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(10),
+            max_attempts: None,
+            capture_failures: 5,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Wait before connect attempt `attempt`, counting from 1, the first is made right away.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        if attempt <= 1 {
+            return Duration::ZERO;
+        }
+        let factor = 1u32.checked_shl(attempt - 2).unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+type Connect = Box<dyn FnMut() -> Result<(Controller, ScreenCapture), ControllerError> + Send>;
+
+/// Keeps a controller connected: notices when its connection dies, reconnects with
+/// exponential backoff and restores the capture and input settings it had.
+pub struct Supervisor {
+    connect: Connect,
+    policy: ReconnectPolicy,
+    connection: Option<(Controller, ScreenCapture)>,
+    /// Last command of every settings kind, sent again after reconnecting.
+    session: Vec<Command>,
+    capture_failures: u32,
+    eventtx: Sender<ConnectionEvent>,
+    events: Receiver<ConnectionEvent>,
+}
+
+impl Supervisor {
+    /// Connect with `connect`, which is called again for every reconnect.
+    pub fn new<F>(connect: F, policy: ReconnectPolicy) -> Result<Self, ControllerError>
+    where
+        F: FnMut() -> Result<(Controller, ScreenCapture), ControllerError> + Send + 'static,
+    {
+        let (eventtx, events) = channel();
+        let mut supervisor = Supervisor {
+            connect: Box::new(connect),
+            policy,
+            connection: None,
+            session: Vec::new(),
+            capture_failures: 0,
+            eventtx,
+            events,
+        };
+
+        supervisor.connection = Some((supervisor.connect)()?);
+        supervisor.emit(ConnectionEvent::Connected);
+
+        Ok(supervisor)
+    }
+
+    /// Supervise the backend at `uri`, see [`Platform::Uri`].
+    pub fn uri(uri: impl Into<String>, policy: ReconnectPolicy) -> Result<Self, ControllerError> {
+        let uri = uri.into();
+        Self::new(move || controller(Platform::Uri(uri.clone())), policy)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Capture of the current connection, it is replaced on every reconnect.
+    pub fn screen_capture(&mut self) -> Option<&mut ScreenCapture> {
+        self.connection.as_mut().map(|(_, screen_cap)| screen_cap)
+    }
+
+    /// Execute on the current connection, reconnecting first if it is dead.
+    ///
+    /// A command that failed because the connection died is sent once more on the new one
+    /// only if the backend knows it was not applied, see [`ControllerError::is_unsent`].
+    /// Otherwise the error is returned after reconnecting, a tap or text may have gone through.
+    pub fn execute(&mut self, command: Command) -> Result<Return, ControllerError> {
+        self.check()?;
+
+        let mut result = self.controller()?.execute(command.clone());
+        if let Err(e) = &result
+            && e.is_disconnected()
+        {
+            self.disconnected(e.to_string());
+            self.reconnect()?;
+            if e.is_unsent() {
+                result = self.controller()?.execute(command.clone());
+            }
+        }
+
+        if result.is_ok() {
+            self.remember(command);
+        }
+        result
+    }
+
+    /// Look at the capture events for a dead connection and reconnect if needed.
+    ///
+    /// This takes the events of [`Self::screen_capture`], nothing else should read them.
+    pub fn check(&mut self) -> Result<(), ControllerError> {
+        if let Some((_, screen_cap)) = &self.connection {
+            let mut error = None;
+            for event in screen_cap.events() {
+                match event {
+                    CaptureEvent::Failed { error: e, .. } => {
+                        self.capture_failures += 1;
+                        error = Some(e);
+                    }
+                    CaptureEvent::Recovered => self.capture_failures = 0,
+                    CaptureEvent::Resized { .. } => {}
+                }
+            }
+
+            if self.capture_failures >= self.policy.capture_failures
+                && let Some(error) = error
+            {
+                self.disconnected(error);
+            }
+        }
+
+        if self.connection.is_none() {
+            self.reconnect()?;
+        }
+        Ok(())
+    }
+
+    /// Next pending connection event.
+    pub fn try_event(&self) -> Option<ConnectionEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Every pending connection event.
+    pub fn events(&self) -> TryIter<'_, ConnectionEvent> {
+        self.events.try_iter()
+    }
+
+    fn controller(&mut self) -> Result<&mut Controller, ControllerError> {
+        self.connection
+            .as_mut()
+            .map(|(controller, _)| controller)
+            .ok_or(ControllerError::Closed)
+    }
+
+    fn emit(&self, event: ConnectionEvent) {
+        let _ = self.eventtx.send(event);
+    }
+
+    fn disconnected(&mut self, error: String) {
+        warn!("Connection lost: {}", error);
+
+        // Dropping the controller also stops its capture thread
+        self.connection = None;
+        self.capture_failures = 0;
+        self.emit(ConnectionEvent::Disconnected { error });
+    }
+
+    fn reconnect(&mut self) -> Result<(), ControllerError> {
+        let mut attempt = 0;
+        let connection = loop {
+            attempt += 1;
+            let retry_in = self.policy.delay(attempt);
+            self.emit(ConnectionEvent::Reconnecting { attempt, retry_in });
+            sleep(retry_in);
+
+            match (self.connect)() {
+                Ok(connection) => break connection,
+                Err(e) => {
+                    warn!("Reconnect attempt {} failed: {}", attempt, e);
+                    if self.policy.max_attempts.is_some_and(|max| attempt >= max) {
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        self.connection = Some(connection);
+
+        // A connection that can't take the session back is no good either, so the events
+        // never show a connection that is up without `Connected`
+        for command in self.session.clone() {
+            if let Err(e) = self.controller()?.execute(command) {
+                self.disconnected(e.to_string());
+                return Err(e);
+            }
+        }
+
+        info!("Reconnected after {} attempts", attempt);
+        self.emit(ConnectionEvent::Connected);
+        Ok(())
+    }
+
+    fn remember(&mut self, command: Command) {
+        if matches!(
+            command,
+            Command::ControlScreenCapture { .. }
+                | Command::SetFrameRate(_)
+                | Command::BindDisplay(_)
+                | Command::SetSwipeProfile(_)
+                | Command::SetInputSpace(_)
+        ) {
+            self.session
+                .retain(|c| discriminant(c) != discriminant(&command));
+            self.session.push(command);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use anyhow::{Result, anyhow};
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::mock::{MockConfig, MockError};
+
+    fn supervised(policy: ReconnectPolicy) -> Result<(Supervisor, MockConfig)> {
+        let mut config =
+            MockConfig::from_images(vec![RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]))]);
+        config.frame_interval = Duration::from_millis(1);
+
+        let connect = config.clone();
+        let supervisor =
+            Supervisor::new(move || controller(Platform::Mock(connect.clone())), policy)?;
+        Ok((supervisor, config))
+    }
+
+    fn policy() -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial: Duration::from_millis(5),
+            max: Duration::from_millis(20),
+            max_attempts: None,
+            capture_failures: 3,
+        }
+    }
+
+    #[test]
+    fn test_reconnect_policy_delay() {
+        let policy = policy();
+        assert_eq!(policy.delay(1), Duration::ZERO);
+        assert_eq!(policy.delay(2), Duration::from_millis(5));
+        assert_eq!(policy.delay(3), Duration::from_millis(10));
+        assert_eq!(policy.delay(5), Duration::from_millis(20));
+        assert_eq!(policy.delay(100), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_reconnect_on_command() -> Result<()> {
+        let (mut supervisor, config) = supervised(policy())?;
+        assert_eq!(supervisor.try_event(), Some(ConnectionEvent::Connected));
+
+        supervisor.execute(Command::ControlScreenCapture { start: true })?;
+        config.link.drop_connections();
+        config.link.refuse(2);
+
+        supervisor.execute(Command::Tab { x: 1, y: 2 })?;
+
+        let events: Vec<_> = supervisor.events().collect();
+        assert_eq!(
+            events,
+            [
+                ConnectionEvent::Disconnected {
+                    error: ControllerError::from(MockError::Disconnected).to_string()
+                },
+                ConnectionEvent::Reconnecting {
+                    attempt: 1,
+                    retry_in: Duration::ZERO
+                },
+                ConnectionEvent::Reconnecting {
+                    attempt: 2,
+                    retry_in: Duration::from_millis(5)
+                },
+                ConnectionEvent::Reconnecting {
+                    attempt: 3,
+                    retry_in: Duration::from_millis(10)
+                },
+                ConnectionEvent::Connected,
+            ]
+        );
+
+        // Capture was started again on the new connection before the tap was resent
+        assert!(matches!(
+            config.log.commands()[..],
+            [
+                Command::ControlScreenCapture { start: true },
+                Command::Tab { x: 1, y: 2 },
+                Command::ControlScreenCapture { start: true },
+                Command::Tab { x: 1, y: 2 },
+            ]
+        ));
+
+        let screen_cap = supervisor
+            .screen_capture()
+            .ok_or(anyhow!("not connected"))?;
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !screen_cap.update() {
+            if Instant::now() > deadline {
+                return Err(anyhow!("no frame after reconnect"));
+            }
+            sleep(Duration::from_millis(1));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconnect_without_resend() -> Result<()> {
+        let (mut supervisor, config) = supervised(policy())?;
+        supervisor.events().for_each(drop);
+
+        // The tap ran, only its answer was lost, so it must not run twice
+        config.link.lose_replies(1);
+        assert!(matches!(
+            supervisor.execute(Command::Tab { x: 1, y: 2 }),
+            Err(ControllerError::MockError(MockError::ReplyLost))
+        ));
+        assert!(supervisor.is_connected());
+        assert_eq!(supervisor.events().last(), Some(ConnectionEvent::Connected));
+        assert!(matches!(
+            config.log.commands()[..],
+            [Command::Tab { x: 1, y: 2 }]
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconnect_on_capture_failures() -> Result<()> {
+        let (mut supervisor, config) = supervised(policy())?;
+        supervisor.execute(Command::ControlScreenCapture { start: true })?;
+        supervisor.events().for_each(drop);
+
+        // Capture retries after 10, 20 and 40ms before the third failure
+        config.link.drop_connections();
+        sleep(Duration::from_millis(150));
+        supervisor.check()?;
+
+        let events: Vec<_> = supervisor.events().collect();
+        assert!(matches!(
+            &events[0],
+            ConnectionEvent::Disconnected { error } if error == &MockError::Disconnected.to_string()
+        ));
+        assert_eq!(events.last(), Some(&ConnectionEvent::Connected));
+        assert!(supervisor.is_connected());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconnect_session_fails() -> Result<()> {
+        let (mut supervisor, config) = supervised(policy())?;
+        supervisor.execute(Command::ControlScreenCapture { start: true })?;
+        supervisor.events().for_each(drop);
+
+        // Connecting works, starting capture again on the new connection does not
+        config.link.drop_connections();
+        config.link.busy(1);
+        assert!(matches!(
+            supervisor.execute(Command::Tab { x: 1, y: 2 }),
+            Err(ControllerError::MockError(MockError::Busy))
+        ));
+        assert!(!supervisor.is_connected());
+        assert_eq!(
+            supervisor.events().last(),
+            Some(ConnectionEvent::Disconnected {
+                error: ControllerError::from(MockError::Busy).to_string()
+            })
+        );
+
+        supervisor.execute(Command::Tab { x: 1, y: 2 })?;
+        assert!(supervisor.is_connected());
+        assert_eq!(supervisor.events().last(), Some(ConnectionEvent::Connected));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconnect_gives_up() -> Result<()> {
+        let (mut supervisor, config) = supervised(ReconnectPolicy {
+            max_attempts: Some(2),
+            ..policy()
+        })?;
+        supervisor.events().for_each(drop);
+
+        config.link.drop_connections();
+        config.link.refuse(5);
+        assert!(matches!(
+            supervisor.execute(Command::Tab { x: 1, y: 2 }),
+            Err(ControllerError::MockError(MockError::Disconnected))
+        ));
+        assert!(!supervisor.is_connected());
+        assert_eq!(supervisor.events().count(), 3);
+
+        config.link.refuse(0);
+        supervisor.execute(Command::Tab { x: 1, y: 2 })?;
+        assert!(supervisor.is_connected());
+        assert_eq!(supervisor.events().last(), Some(ConnectionEvent::Connected));
+
+        Ok(())
+    }
+}
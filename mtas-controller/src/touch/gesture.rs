@@ -0,0 +1,436 @@
+use std::{
+    collections::BTreeSet,
+    f64::consts::PI,
+    time::{Duration, Instant},
+};
+
+use crate::touch::{SwipeProfile, TouchEvent, TouchSink, sleep_until};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A finger position at `t` after the gesture started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keypoint {
+    pub t: Duration,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// One finger of a gesture, pressed at the first keypoint and released at the last.
+/// Positions between keypoints are interpolated linearly.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FingerTrack {
+    pub contact: u32,
+    pub keypoints: Vec<Keypoint>,
+}
+
+/// Several fingers moving at once, executed with `Command::Gesture`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Gesture {
+    pub tracks: Vec<FingerTrack>,
+}
+
+#[derive(Error, Debug)]
+pub enum GestureError {
+    #[error("Gesture Has No Keypoints")]
+    Empty,
+
+    #[error("Gesture Uses Contact {0} More Than Once")]
+    DuplicateContact(u32),
+
+    #[error("Keypoints Of Contact {0} Go Back In Time")]
+    Unordered(u32),
+}
+
+impl FingerTrack {
+    pub fn new(contact: u32, keypoints: Vec<Keypoint>) -> Self {
+        FingerTrack { contact, keypoints }
+    }
+
+    /// Straight move from `from` to `to` over `t`.
+    pub fn line(contact: u32, from: (i32, i32), to: (i32, i32), t: Duration) -> Self {
+        FingerTrack::new(
+            contact,
+            vec![
+                Keypoint {
+                    t: Duration::ZERO,
+                    x: from.0,
+                    y: from.1,
+                },
+                Keypoint {
+                    t,
+                    x: to.0,
+                    y: to.1,
+                },
+            ],
+        )
+    }
+
+    fn position(&self, t: Duration) -> (i32, i32) {
+        let next = self.keypoints.partition_point(|k| k.t <= t);
+        match (
+            self.keypoints.get(next.wrapping_sub(1)),
+            self.keypoints.get(next),
+        ) {
+            (Some(a), Some(b)) => {
+                let p = (t - a.t).as_secs_f64() / (b.t - a.t).as_secs_f64();
+                (
+                    a.x + ((b.x - a.x) as f64 * p).round() as i32,
+                    a.y + ((b.y - a.y) as f64 * p).round() as i32,
+                )
+            }
+            (Some(k), None) | (None, Some(k)) => (k.x, k.y),
+            (None, None) => (0, 0),
+        }
+    }
+}
+
+impl Gesture {
+    pub fn new(tracks: Vec<FingerTrack>) -> Self {
+        Gesture { tracks }
+    }
+
+    /// Two fingers on a horizontal line through `center`, moving from `start_radius` to
+    /// `end_radius` away from it.
+    pub fn pinch(center: (i32, i32), start_radius: i32, end_radius: i32, t: Duration) -> Self {
+        let (cx, cy) = center;
+        Gesture::new(vec![
+            FingerTrack::line(0, (cx - start_radius, cy), (cx - end_radius, cy), t),
+            FingerTrack::line(1, (cx + start_radius, cy), (cx + end_radius, cy), t),
+        ])
+    }
+
+    /// Fingers move together, zooms out.
+    pub fn pinch_in(center: (i32, i32), radius: i32, t: Duration) -> Self {
+        Gesture::pinch(center, radius, radius / 4, t)
+    }
+
+    /// Fingers move apart, zooms in.
+    pub fn pinch_out(center: (i32, i32), radius: i32, t: Duration) -> Self {
+        Gesture::pinch(center, radius / 4, radius, t)
+    }
+
+    /// Two opposite fingers turning `degrees` around `center`, clockwise on screen.
+    pub fn rotate(center: (i32, i32), radius: i32, degrees: f64, t: Duration) -> Self {
+        // A keypoint every 10 degrees keeps the chords close to the arc
+        let segments = (degrees.abs() / 10.0).ceil().max(1.0) as u32;
+
+        let arc = |contact: u32, phase: f64| {
+            let keypoints = (0..=segments)
+                .map(|i| {
+                    let angle = phase + degrees.to_radians() * i as f64 / segments as f64;
+                    Keypoint {
+                        t: t * i / segments,
+                        x: center.0 + (radius as f64 * angle.cos()).round() as i32,
+                        y: center.1 + (radius as f64 * angle.sin()).round() as i32,
+                    }
+                })
+                .collect();
+            FingerTrack::new(contact, keypoints)
+        };
+
+        Gesture::new(vec![arc(0, PI), arc(1, 0.0)])
+    }
+
+    /// Two fingers `spacing` apart dragging from `from` to `to`.
+    pub fn two_finger_pan(from: (i32, i32), to: (i32, i32), spacing: i32, t: Duration) -> Self {
+        let half = spacing / 2;
+        Gesture::new(vec![
+            FingerTrack::line(0, (from.0 - half, from.1), (to.0 - half, to.1), t),
+            FingerTrack::line(1, (from.0 + half, from.1), (to.0 + half, to.1), t),
+        ])
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.tracks
+            .iter()
+            .filter_map(|track| track.keypoints.last())
+            .map(|k| k.t)
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn validate(&self) -> Result<(), GestureError> {
+        let mut contacts = BTreeSet::new();
+
+        for track in &self.tracks {
+            if !contacts.insert(track.contact) {
+                return Err(GestureError::DuplicateContact(track.contact));
+            }
+            if track.keypoints.windows(2).any(|w| w[1].t < w[0].t) {
+                return Err(GestureError::Unordered(track.contact));
+            }
+        }
+
+        if self.tracks.iter().all(|track| track.keypoints.is_empty()) {
+            return Err(GestureError::Empty);
+        }
+
+        Ok(())
+    }
+
+    /// Every touch event of the gesture in time order, moves are sampled `rate` times a second,
+    /// limited like [`SwipeProfile::sample_rate`].
+    pub fn timeline(&self, rate: f64) -> Result<Vec<(Duration, TouchEvent)>, GestureError> {
+        self.validate()?;
+
+        let step = Duration::from_secs_f64(1.0 / SwipeProfile::sample_rate(rate));
+        let mut events = Vec::new();
+
+        for track in &self.tracks {
+            let (Some(first), Some(last)) = (track.keypoints.first(), track.keypoints.last())
+            else {
+                continue;
+            };
+            let contact = track.contact;
+
+            events.push((
+                first.t,
+                TouchEvent::Down {
+                    contact,
+                    x: first.x,
+                    y: first.y,
+                },
+            ));
+
+            let mut t = first.t + step;
+            while t < last.t {
+                let (x, y) = track.position(t);
+                events.push((t, TouchEvent::Move { contact, x, y }));
+                t += step;
+            }
+
+            if last.t > first.t {
+                events.push((
+                    last.t,
+                    TouchEvent::Move {
+                        contact,
+                        x: last.x,
+                        y: last.y,
+                    },
+                ));
+            }
+            events.push((last.t, TouchEvent::Up { contact }));
+        }
+
+        // Stable, so every finger keeps its own down/move/up order
+        events.sort_by_key(|(t, _)| *t);
+
+        Ok(events)
+    }
+}
+
+/// Play `timeline` on `sink` in real time, releasing every pressed finger if a step fails.
+pub fn run_timeline<S: TouchSink>(
+    sink: &mut S,
+    timeline: &[(Duration, TouchEvent)],
+) -> Result<(), S::Error> {
+    let start = Instant::now();
+    let mut pressed = BTreeSet::new();
+
+    for (at, event) in timeline {
+        sleep_until(start + *at);
+
+        let result = match *event {
+            TouchEvent::Down { contact, x, y } => {
+                pressed.insert(contact);
+                sink.touch_down(contact, x, y)
+            }
+            TouchEvent::Move { contact, x, y } => sink.touch_move(contact, x, y),
+            TouchEvent::Up { contact } => {
+                pressed.remove(&contact);
+                sink.touch_up(contact)
+            }
+        };
+
+        if let Err(e) = result {
+            for contact in pressed {
+                let _ = sink.touch_up(contact);
+            }
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_pinch_timeline() -> Result<(), GestureError> {
+        let timeline = Gesture::pinch_in((500, 300), 200, ms(40)).timeline(50.0)?;
+
+        let events: Vec<_> = timeline.iter().map(|(_, e)| *e).collect();
+        assert_eq!(
+            events,
+            vec![
+                TouchEvent::Down {
+                    contact: 0,
+                    x: 300,
+                    y: 300
+                },
+                TouchEvent::Down {
+                    contact: 1,
+                    x: 700,
+                    y: 300
+                },
+                TouchEvent::Move {
+                    contact: 0,
+                    x: 375,
+                    y: 300
+                },
+                TouchEvent::Move {
+                    contact: 1,
+                    x: 625,
+                    y: 300
+                },
+                TouchEvent::Move {
+                    contact: 0,
+                    x: 450,
+                    y: 300
+                },
+                TouchEvent::Up { contact: 0 },
+                TouchEvent::Move {
+                    contact: 1,
+                    x: 550,
+                    y: 300
+                },
+                TouchEvent::Up { contact: 1 },
+            ]
+        );
+        assert_eq!(timeline[2].0, ms(20));
+        assert_eq!(timeline.last().unwrap().0, ms(40));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timeline_rate_limit() -> Result<(), GestureError> {
+        let gesture = Gesture::pinch_out((0, 0), 100, ms(100));
+        for rate in [f64::INFINITY, 1e12, f64::NAN] {
+            let moves = (gesture.timeline(rate)?.len() - 4) / 2;
+            assert!(moves <= SwipeProfile::MAX_RATE as usize / 10, "{rate}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_keypoints() {
+        let gesture = Gesture::rotate((0, 0), 100, 90.0, ms(90));
+
+        assert_eq!(gesture.tracks[0].keypoints.len(), 10);
+        assert_eq!(
+            gesture.tracks[0].keypoints.last(),
+            Some(&Keypoint {
+                t: ms(90),
+                x: 0,
+                y: -100
+            })
+        );
+        assert_eq!(
+            gesture.tracks[1].keypoints.last(),
+            Some(&Keypoint {
+                t: ms(90),
+                x: 0,
+                y: 100
+            })
+        );
+        assert_eq!(gesture.duration(), ms(90));
+    }
+
+    #[test]
+    fn test_staggered_tracks() -> Result<(), GestureError> {
+        let gesture = Gesture::new(vec![
+            FingerTrack::line(3, (0, 0), (0, 0), ms(0)),
+            FingerTrack::new(
+                5,
+                vec![
+                    Keypoint {
+                        t: ms(10),
+                        x: 0,
+                        y: 0,
+                    },
+                    Keypoint {
+                        t: ms(30),
+                        x: 40,
+                        y: 0,
+                    },
+                ],
+            ),
+        ]);
+
+        let timeline = gesture.timeline(100.0)?;
+        assert_eq!(
+            timeline,
+            vec![
+                (
+                    ms(0),
+                    TouchEvent::Down {
+                        contact: 3,
+                        x: 0,
+                        y: 0
+                    }
+                ),
+                (ms(0), TouchEvent::Up { contact: 3 }),
+                (
+                    ms(10),
+                    TouchEvent::Down {
+                        contact: 5,
+                        x: 0,
+                        y: 0
+                    }
+                ),
+                (
+                    ms(20),
+                    TouchEvent::Move {
+                        contact: 5,
+                        x: 20,
+                        y: 0
+                    }
+                ),
+                (
+                    ms(30),
+                    TouchEvent::Move {
+                        contact: 5,
+                        x: 40,
+                        y: 0
+                    }
+                ),
+                (ms(30), TouchEvent::Up { contact: 5 }),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_gestures() {
+        assert!(matches!(
+            Gesture::default().validate(),
+            Err(GestureError::Empty)
+        ));
+
+        let duplicate = Gesture::new(vec![
+            FingerTrack::line(1, (0, 0), (1, 1), ms(5)),
+            FingerTrack::line(1, (2, 2), (3, 3), ms(5)),
+        ]);
+        assert!(matches!(
+            duplicate.validate(),
+            Err(GestureError::DuplicateContact(1))
+        ));
+
+        let mut backwards = Gesture::new(vec![FingerTrack::line(0, (0, 0), (1, 1), ms(5))]);
+        backwards.tracks[0].keypoints.reverse();
+        assert!(matches!(
+            backwards.validate(),
+            Err(GestureError::Unordered(0))
+        ));
+    }
+}
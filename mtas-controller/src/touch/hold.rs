@@ -0,0 +1,190 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use crate::touch::{Gesture, TouchSink};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HoldError {
+    #[error("Contact {0} Is Already Down")]
+    AlreadyDown(u32),
+
+    #[error("Contact {0} Is Not Down")]
+    NotDown(u32),
+}
+
+/// A [`TouchSink`] that keeps fingers pressed across commands, used by
+/// `Command::TouchDown`, `Command::TouchMove` and `Command::TouchUp`.
+///
+/// Backends release every held finger when a command fails and when they are dropped.
+pub trait HoldTouch: TouchSink<Error: From<HoldError>> {
+    /// Contacts pressed and not released yet.
+    fn held(&mut self) -> &mut BTreeSet<u32>;
+
+    /// Lowest contact that is not held.
+    fn free_contact(&mut self) -> u32 {
+        let held = self.held();
+        (0..).find(|contact| !held.contains(contact)).unwrap_or(0)
+    }
+
+    /// Fail if `gesture` uses a held contact, playing it would release that finger.
+    fn check_gesture(&mut self, gesture: &Gesture) -> Result<(), Self::Error> {
+        let held = self.held();
+        match gesture
+            .tracks
+            .iter()
+            .find(|track| held.contains(&track.contact))
+        {
+            Some(track) => Err(HoldError::AlreadyDown(track.contact).into()),
+            None => Ok(()),
+        }
+    }
+
+    fn hold_down(&mut self, contact: u32, x: i32, y: i32) -> Result<(), Self::Error> {
+        if !self.held().insert(contact) {
+            return Err(HoldError::AlreadyDown(contact).into());
+        }
+        self.touch_down(contact, x, y).inspect_err(|_| {
+            self.held().remove(&contact);
+        })
+    }
+
+    fn hold_move(&mut self, contact: u32, x: i32, y: i32) -> Result<(), Self::Error> {
+        if !self.held().contains(&contact) {
+            return Err(HoldError::NotDown(contact).into());
+        }
+        self.touch_move(contact, x, y)
+    }
+
+    fn hold_up(&mut self, contact: u32) -> Result<(), Self::Error> {
+        if !self.held().remove(&contact) {
+            return Err(HoldError::NotDown(contact).into());
+        }
+        self.touch_up(contact)
+    }
+
+    /// Release every held contact, all of them are tried and the first error is returned.
+    fn release_held(&mut self) -> Result<(), Self::Error> {
+        let mut result = Ok(());
+        for contact in std::mem::take(self.held()) {
+            if let Err(e) = self.touch_up(contact)
+                && result.is_ok()
+            {
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+/// Press `contact` at `(x, y)` for `hold`, then release it.
+pub fn long_press<S: TouchSink>(
+    sink: &mut S,
+    contact: u32,
+    x: i32,
+    y: i32,
+    hold: Duration,
+) -> Result<(), S::Error> {
+    sink.touch_down(contact, x, y)?;
+    std::thread::sleep(hold);
+    sink.touch_up(contact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::touch::TouchEvent;
+
+    #[derive(Default)]
+    struct Recorder {
+        held: BTreeSet<u32>,
+        events: Vec<TouchEvent>,
+        fail_up: bool,
+    }
+
+    #[derive(Debug)]
+    enum RecorderError {
+        Hold,
+        Up,
+    }
+
+    impl From<HoldError> for RecorderError {
+        fn from(_: HoldError) -> Self {
+            RecorderError::Hold
+        }
+    }
+
+    impl TouchSink for Recorder {
+        type Error = RecorderError;
+
+        fn touch_down(&mut self, contact: u32, x: i32, y: i32) -> Result<(), RecorderError> {
+            self.events.push(TouchEvent::Down { contact, x, y });
+            Ok(())
+        }
+
+        fn touch_move(&mut self, contact: u32, x: i32, y: i32) -> Result<(), RecorderError> {
+            self.events.push(TouchEvent::Move { contact, x, y });
+            Ok(())
+        }
+
+        fn touch_up(&mut self, contact: u32) -> Result<(), RecorderError> {
+            self.events.push(TouchEvent::Up { contact });
+            if self.fail_up {
+                return Err(RecorderError::Up);
+            }
+            Ok(())
+        }
+    }
+
+    impl HoldTouch for Recorder {
+        fn held(&mut self) -> &mut BTreeSet<u32> {
+            &mut self.held
+        }
+    }
+
+    #[test]
+    fn test_hold_tracking() {
+        let mut sink = Recorder::default();
+
+        sink.hold_down(0, 1, 2).unwrap();
+        assert_eq!(sink.free_contact(), 1);
+        assert!(matches!(sink.hold_down(0, 1, 2), Err(RecorderError::Hold)));
+        assert!(matches!(sink.hold_move(1, 1, 2), Err(RecorderError::Hold)));
+        assert!(matches!(sink.hold_up(1), Err(RecorderError::Hold)));
+
+        sink.hold_move(0, 3, 4).unwrap();
+        sink.hold_up(0).unwrap();
+        assert_eq!(sink.free_contact(), 0);
+
+        assert_eq!(
+            sink.events,
+            vec![
+                TouchEvent::Down {
+                    contact: 0,
+                    x: 1,
+                    y: 2
+                },
+                TouchEvent::Move {
+                    contact: 0,
+                    x: 3,
+                    y: 4
+                },
+                TouchEvent::Up { contact: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_release_held() {
+        let mut sink = Recorder::default();
+        sink.hold_down(2, 0, 0).unwrap();
+        sink.hold_down(5, 0, 0).unwrap();
+        sink.fail_up = true;
+
+        assert!(matches!(sink.release_held(), Err(RecorderError::Up)));
+        assert!(sink.held.is_empty());
+        assert_eq!(
+            sink.events[2..],
+            [TouchEvent::Up { contact: 2 }, TouchEvent::Up { contact: 5 }]
+        );
+    }
+}
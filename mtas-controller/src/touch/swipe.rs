@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::touch::PathPoint;
+
+/// Progress curve of a swipe over its duration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Slow start and stop, like a human drag.
+    EaseInOut,
+    /// Runs past the target and settles back, triggers inertial scrolling.
+    Overshoot,
+}
+
+impl Easing {
+    /// Map linear progress `t` in `0..=1` to eased progress, `0` and `1` are fixed points.
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::Overshoot => {
+                const C1: f64 = 1.70158;
+                const C3: f64 = C1 + 1.0;
+                1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2)
+            }
+        }
+    }
+}
+
+/// How `Command::Scroll` moves the finger, set with `Command::SetSwipeProfile`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SwipeProfile {
+    pub easing: Easing,
+    /// Touch-move events per second.
+    pub rate: f64,
+}
+
+impl Default for SwipeProfile {
+    fn default() -> Self {
+        SwipeProfile {
+            easing: Easing::default(),
+            rate: 60.0,
+        }
+    }
+}
+
+impl SwipeProfile {
+    /// Highest rate paths are sampled at, touch drivers take nothing faster and a huge rate
+    /// would only allocate a huge path.
+    pub const MAX_RATE: f64 = 1000.0;
+
+    /// `rate` within `1..=MAX_RATE`, the default rate for NaN.
+    pub fn sample_rate(rate: f64) -> f64 {
+        if rate.is_nan() {
+            return SwipeProfile::default().rate;
+        }
+        rate.clamp(1.0, Self::MAX_RATE)
+    }
+
+    /// The finger path from `(x1, y1)` to `(x2, y2)` spread over `t`.
+    ///
+    /// The first point is the touch down at offset 0, the last one lands on the target at `t`.
+    pub fn path(&self, x1: i32, y1: i32, x2: i32, y2: i32, t: Duration) -> Vec<PathPoint> {
+        let rate = Self::sample_rate(self.rate);
+        let steps = (t.as_secs_f64() * rate).round().max(1.0) as u32;
+
+        let mut path = Vec::with_capacity(steps as usize + 1);
+        path.push(PathPoint {
+            at: Duration::ZERO,
+            x: x1,
+            y: y1,
+        });
+
+        for i in 1..=steps {
+            let p = self.easing.apply(i as f64 / steps as f64);
+            path.push(PathPoint {
+                at: t * i / steps,
+                x: x1 + ((x2 - x1) as f64 * p).round() as i32,
+                y: y1 + ((y2 - y1) as f64 * p).round() as i32,
+            });
+        }
+
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in [Easing::Linear, Easing::EaseInOut, Easing::Overshoot] {
+            assert!(easing.apply(0.0).abs() < 1e-9, "{easing:?}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9, "{easing:?}");
+        }
+
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 1e-9);
+        assert!(Easing::EaseInOut.apply(0.1) < 0.1);
+        assert!(Easing::Overshoot.apply(0.8) > 1.0);
+    }
+
+    #[test]
+    fn test_swipe_path_timing() {
+        let profile = SwipeProfile {
+            easing: Easing::Linear,
+            rate: 100.0,
+        };
+
+        let path = profile.path(0, 100, 100, 0, Duration::from_millis(50));
+
+        assert_eq!(path.len(), 6);
+        assert_eq!(
+            path[0],
+            PathPoint {
+                at: Duration::ZERO,
+                x: 0,
+                y: 100,
+            }
+        );
+        assert_eq!(
+            path[1],
+            PathPoint {
+                at: Duration::from_millis(10),
+                x: 20,
+                y: 80,
+            }
+        );
+        assert_eq!(
+            path[5],
+            PathPoint {
+                at: Duration::from_millis(50),
+                x: 100,
+                y: 0,
+            }
+        );
+        assert!(path.windows(2).all(|w| w[0].at < w[1].at));
+    }
+
+    #[test]
+    fn test_swipe_path_overshoot() {
+        let profile = SwipeProfile {
+            easing: Easing::Overshoot,
+            rate: 60.0,
+        };
+
+        let path = profile.path(0, 0, 100, 0, Duration::from_millis(500));
+
+        assert!(path.iter().any(|p| p.x > 100));
+        assert_eq!(path.last().map(|p| p.x), Some(100));
+    }
+
+    #[test]
+    fn test_swipe_path_rate_limit() {
+        for rate in [f64::INFINITY, f64::MAX, f64::NAN, -5.0] {
+            let profile = SwipeProfile {
+                easing: Easing::Linear,
+                rate,
+            };
+            let path = profile.path(0, 0, 10, 0, Duration::from_secs(1));
+            assert!(path.len() <= SwipeProfile::MAX_RATE as usize + 1, "{rate}");
+            assert_eq!(path.last().map(|p| p.x), Some(10));
+        }
+    }
+
+    #[test]
+    fn test_swipe_path_instant() {
+        let path = SwipeProfile::default().path(1, 2, 3, 4, Duration::ZERO);
+
+        assert_eq!(path.len(), 2);
+        assert_eq!((path[1].x, path[1].y), (3, 4));
+    }
+}
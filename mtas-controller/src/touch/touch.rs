@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+/// Low level multi-touch injection, implemented by every backend that can move a finger.
+///
+/// `contact` is a 0 based finger id, backends map it to their own numbering.
+pub trait TouchSink {
+    type Error;
+
+    fn touch_down(&mut self, contact: u32, x: i32, y: i32) -> Result<(), Self::Error>;
+    fn touch_move(&mut self, contact: u32, x: i32, y: i32) -> Result<(), Self::Error>;
+    fn touch_up(&mut self, contact: u32) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchEvent {
+    Down { contact: u32, x: i32, y: i32 },
+    Move { contact: u32, x: i32, y: i32 },
+    Up { contact: u32 },
+}
+
+/// Sleep until `deadline`, scheduling against a fixed start keeps long paths from drifting.
+pub fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now {
+        std::thread::sleep(deadline - now);
+    }
+}
+
+/// One point of a finger path, `at` is relative to the start of the path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathPoint {
+    pub at: Duration,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Press at the first point, move through the rest on time and release at the end.
+///
+/// The finger is released even when a move fails, so the device is never left pressed.
+pub fn run_path<S: TouchSink>(
+    sink: &mut S,
+    contact: u32,
+    path: &[PathPoint],
+) -> Result<(), S::Error> {
+    let Some((first, rest)) = path.split_first() else {
+        return Ok(());
+    };
+
+    let start = Instant::now();
+    sink.touch_down(contact, first.x, first.y)?;
+
+    for point in rest {
+        sleep_until(start + point.at);
+        if let Err(e) = sink.touch_move(contact, point.x, point.y) {
+            let _ = sink.touch_up(contact);
+            return Err(e);
+        }
+    }
+
+    sink.touch_up(contact)
+}
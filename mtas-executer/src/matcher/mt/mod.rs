@@ -1,5 +1,6 @@
 use image::{GrayImage, ImageBuffer, Luma, open};
 use imageproc::template_matching::{MatchTemplateMethod, match_template};
+use mtas_utils::{Normalized, Point};
 use std::path::PathBuf;
 use strum::{EnumIter, IntoEnumIterator};
 
@@ -16,6 +17,13 @@ pub struct ButtonMatch {
     pub confidence: f64,
 }
 
+impl ButtonMatch {
+    /// Center of the button, ready for `Command::TabAt`.
+    pub fn point(&self) -> Point {
+        Normalized::new(self.x, self.y).into()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PageMatch {
     pub page: MTPage,
@@ -275,6 +283,7 @@ mod tests {
         ImageBuffer::from_pixel(800, 600, Luma([0]))
     }
 
+    #[allow(dead_code)]
     enum ImageQuality {
         Good,
         Dark,
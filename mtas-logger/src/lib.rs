@@ -14,7 +14,7 @@ use tracing_appender::non_blocking::WorkerGuard;
 /// and may cause log events to be lost.
 ///
 /// Typical usage:
-/// ```rust
+/// ```ignore
 /// fn main() {
 ///     let _guard = mtas_logger::set_logger(std::io::stdout())?;
 ///     info!("Hello, world!");
@@ -0,0 +1,204 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// A point and where a transform should take it.
+pub type PointPair = ((f64, f64), (f64, f64));
+
+/// `x' = a * x + b * y + c`, `y' = d * x + e * y + f`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Affine::IDENTITY
+    }
+}
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 0.0,
+        e: 1.0,
+        f: 0.0,
+    };
+
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.a * x + self.b * y + self.c,
+            self.d * x + self.e * y + self.f,
+        )
+    }
+
+    /// `None` if the transform squashes the plane onto a line.
+    pub fn inverse(&self) -> Option<Affine> {
+        let det = self.a * self.e - self.b * self.d;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        let (a, b, d, e) = (self.e / det, -self.b / det, -self.d / det, self.a / det);
+        Some(Affine {
+            a,
+            b,
+            c: -(a * self.c + b * self.f),
+            d,
+            e,
+            f: -(d * self.c + e * self.f),
+        })
+    }
+
+    /// Least squares transform taking every `from` onto its `to`.
+    ///
+    /// `None` with fewer than three pairs or when every `from` lies on one line.
+    pub fn fit(pairs: &[PointPair]) -> Option<Affine> {
+        if pairs.len() < 3 {
+            return None;
+        }
+
+        // Centered sums keep the normal equations well conditioned for pixel sized values
+        let n = pairs.len() as f64;
+        let mean = |f: fn(&PointPair) -> f64| pairs.iter().map(f).sum::<f64>() / n;
+        let (mx, my) = (mean(|p| p.0.0), mean(|p| p.0.1));
+        let (mu, mv) = (mean(|p| p.1.0), mean(|p| p.1.1));
+
+        let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+        let (mut sxu, mut syu, mut sxv, mut syv) = (0.0, 0.0, 0.0, 0.0);
+        for ((x, y), (u, v)) in pairs {
+            let (x, y, u, v) = (x - mx, y - my, u - mu, v - mv);
+            sxx += x * x;
+            sxy += x * y;
+            syy += y * y;
+            sxu += x * u;
+            syu += y * u;
+            sxv += x * v;
+            syv += y * v;
+        }
+
+        let det = sxx * syy - sxy * sxy;
+        if det.abs() < 1e-9 * (sxx * syy).max(1.0) {
+            return None;
+        }
+
+        let a = (sxu * syy - syu * sxy) / det;
+        let b = (syu * sxx - sxu * sxy) / det;
+        let d = (sxv * syy - syv * sxy) / det;
+        let e = (syv * sxx - sxv * sxy) / det;
+
+        Some(Affine {
+            a,
+            b,
+            c: mu - a * mx - b * my,
+            d,
+            e,
+            f: mv - d * mx - e * my,
+        })
+    }
+
+    /// Root mean square distance between each mapped `from` and its `to`.
+    pub fn rms_error(&self, pairs: &[PointPair]) -> f64 {
+        if pairs.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f64 = pairs
+            .iter()
+            .map(|(from, to)| {
+                let (x, y) = self.apply(from.0, from.1);
+                (x - to.0).powi(2) + (y - to.1).powi(2)
+            })
+            .sum();
+        (sum / pairs.len() as f64).sqrt()
+    }
+}
+
+/// The six coefficients `a b c d e f` separated by spaces.
+impl Display for Affine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}",
+            self.a, self.b, self.c, self.d, self.e, self.f
+        )
+    }
+}
+
+impl FromStr for Affine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let values = s
+            .split_whitespace()
+            .map(|v| v.parse::<f64>().map_err(|e| format!("{v}: {e}")))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let [a, b, c, d, e, f] = values[..] else {
+            return Err(format!("expected 6 coefficients, got {}", values.len()));
+        };
+        Ok(Affine { a, b, c, d, e, f })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6
+    }
+
+    #[test]
+    fn test_fit_and_inverse() {
+        // Quarter turn, half scale and an offset
+        let known = Affine {
+            a: 0.0,
+            b: -0.5,
+            c: 640.0,
+            d: 0.5,
+            e: 0.0,
+            f: 10.0,
+        };
+        let pairs: Vec<_> = [(0.0, 0.0), (100.0, 0.0), (0.0, 200.0), (300.0, 400.0)]
+            .into_iter()
+            .map(|p| (p, known.apply(p.0, p.1)))
+            .collect();
+
+        let fitted = Affine::fit(&pairs).unwrap();
+        assert!(fitted.rms_error(&pairs) < 1e-6);
+        assert!(close(fitted.apply(50.0, 60.0), known.apply(50.0, 60.0)));
+
+        let (x, y) = fitted.apply(12.0, 34.0);
+        assert!(close(fitted.inverse().unwrap().apply(x, y), (12.0, 34.0)));
+    }
+
+    #[test]
+    fn test_fit_degenerate() {
+        let line: Vec<_> = (0..4).map(|i| ((i as f64, i as f64), (0.0, 0.0))).collect();
+        assert_eq!(Affine::fit(&line), None);
+        assert_eq!(Affine::fit(&line[..2]), None);
+    }
+
+    #[test]
+    fn test_round_trip_text() {
+        let affine = Affine {
+            a: 1.5,
+            b: -0.25,
+            c: 3.0,
+            d: 0.0,
+            e: 2.0,
+            f: -7.125,
+        };
+        assert_eq!(affine.to_string().parse::<Affine>(), Ok(affine));
+        assert!("1 2 3".parse::<Affine>().is_err());
+        assert!("1 2 3 4 5 x".parse::<Affine>().is_err());
+    }
+}
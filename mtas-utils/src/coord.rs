@@ -0,0 +1,429 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Affine;
+
+/// Width and height of a coordinate space in pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How far the captured frame is turned clockwise from the input device's natural orientation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Rotation {
+    #[default]
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+/// Position as a fraction of the captured frame, `0..=1` from the top-left, what the
+/// matcher reports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Normalized {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Pixel of the captured frame, top row first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CapturePoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Pixel of the input device in its natural orientation, what touch commands send.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InputPoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A point in any of the three spaces, resolved with a [`CoordSpace`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Point {
+    Normalized(Normalized),
+    Capture(CapturePoint),
+    Input(InputPoint),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CoordError {
+    #[error("Point ({x}, {y}) Is Outside The {space} Space")]
+    OutOfBounds { x: f64, y: f64, space: &'static str },
+
+    #[error("Coordinate Space Has No Pixels")]
+    EmptySpace,
+
+    #[error("Calibration Can Not Be Inverted")]
+    Singular,
+}
+
+/// Capture and input resolution of a device, converts points between them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CoordSpace {
+    pub capture: Size,
+    /// In the natural orientation, so swapped against `capture` when turned by 90 or 270.
+    pub input: Size,
+    pub rotation: Rotation,
+    /// Measured mapping from capture pixels to input pixels, replaces the one given by the
+    /// sizes and `rotation` when set.
+    pub calibration: Option<Affine>,
+}
+
+impl Size {
+    pub fn new(width: u32, height: u32) -> Self {
+        Size { width, height }
+    }
+
+    /// The same space turned by a quarter, width and height swapped.
+    pub fn transposed(self) -> Self {
+        Size::new(self.height, self.width)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Center of pixel `(x, y)` as a fraction of the size.
+    fn normalize(&self, x: i32, y: i32, space: &'static str) -> Result<(f64, f64), CoordError> {
+        if self.is_empty() {
+            return Err(CoordError::EmptySpace);
+        }
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return Err(CoordError::OutOfBounds {
+                x: x as f64,
+                y: y as f64,
+                space,
+            });
+        }
+        Ok((
+            (x as f64 + 0.5) / self.width as f64,
+            (y as f64 + 0.5) / self.height as f64,
+        ))
+    }
+
+    /// Pixel containing the fraction `(x, y)`, `1.0` falls into the last pixel.
+    ///
+    /// Fractions on a pixel edge go to the pixel after it, with some slack for float error,
+    /// so scaling by a whole factor always lands on the same pixel.
+    fn pixel(&self, x: f64, y: f64) -> Result<(i32, i32), CoordError> {
+        if self.is_empty() {
+            return Err(CoordError::EmptySpace);
+        }
+        let at = |n: f64, len: u32| ((n * len as f64 + 1e-6) as u32).min(len - 1) as i32;
+        Ok((at(x, self.width), at(y, self.height)))
+    }
+}
+
+impl Rotation {
+    /// From Android's `Surface.ROTATION_*` index, wraps around.
+    pub fn from_quarter_turns(turns: u32) -> Self {
+        match turns % 4 {
+            0 => Rotation::R0,
+            1 => Rotation::R90,
+            2 => Rotation::R180,
+            _ => Rotation::R270,
+        }
+    }
+
+    pub fn is_transposed(&self) -> bool {
+        matches!(self, Rotation::R90 | Rotation::R270)
+    }
+
+    /// Fraction of the input space shown at the capture fraction `(x, y)`.
+    fn capture_to_input(&self, x: f64, y: f64) -> (f64, f64) {
+        match self {
+            Rotation::R0 => (x, y),
+            Rotation::R90 => (y, 1.0 - x),
+            Rotation::R180 => (1.0 - x, 1.0 - y),
+            Rotation::R270 => (1.0 - y, x),
+        }
+    }
+
+    fn input_to_capture(&self, x: f64, y: f64) -> (f64, f64) {
+        match self {
+            Rotation::R0 => (x, y),
+            Rotation::R90 => (1.0 - y, x),
+            Rotation::R180 => (1.0 - x, 1.0 - y),
+            Rotation::R270 => (y, 1.0 - x),
+        }
+    }
+}
+
+impl Normalized {
+    pub fn new(x: f64, y: f64) -> Self {
+        Normalized { x, y }
+    }
+
+    fn check(&self) -> Result<(), CoordError> {
+        if (0.0..=1.0).contains(&self.x) && (0.0..=1.0).contains(&self.y) {
+            Ok(())
+        } else {
+            Err(CoordError::OutOfBounds {
+                x: self.x,
+                y: self.y,
+                space: "Normalized",
+            })
+        }
+    }
+}
+
+impl CapturePoint {
+    pub fn new(x: i32, y: i32) -> Self {
+        CapturePoint { x, y }
+    }
+}
+
+impl InputPoint {
+    pub fn new(x: i32, y: i32) -> Self {
+        InputPoint { x, y }
+    }
+}
+
+impl From<Normalized> for Point {
+    fn from(point: Normalized) -> Self {
+        Point::Normalized(point)
+    }
+}
+
+impl From<CapturePoint> for Point {
+    fn from(point: CapturePoint) -> Self {
+        Point::Capture(point)
+    }
+}
+
+impl From<InputPoint> for Point {
+    fn from(point: InputPoint) -> Self {
+        Point::Input(point)
+    }
+}
+
+impl CoordSpace {
+    pub fn new(capture: Size, input: Size, rotation: Rotation) -> Self {
+        CoordSpace {
+            capture,
+            input,
+            rotation,
+            calibration: None,
+        }
+    }
+
+    pub fn with_calibration(self, calibration: Affine) -> Self {
+        CoordSpace {
+            calibration: Some(calibration),
+            ..self
+        }
+    }
+
+    /// Input and capture share one resolution and orientation.
+    pub fn identity(size: Size) -> Self {
+        CoordSpace::new(size, size, Rotation::R0)
+    }
+
+    /// Where `point` is on the captured frame, as a fraction of it.
+    pub fn to_normalized(&self, point: impl Into<Point>) -> Result<Normalized, CoordError> {
+        let (x, y) = match point.into() {
+            Point::Normalized(point) => {
+                point.check()?;
+                (point.x, point.y)
+            }
+            Point::Capture(point) => self.capture.normalize(point.x, point.y, "Capture")?,
+            Point::Input(point) => {
+                let (x, y) = self.input.normalize(point.x, point.y, "Input")?;
+                match self.calibration {
+                    Some(calibration) => {
+                        let inverse = calibration.inverse().ok_or(CoordError::Singular)?;
+                        let (x, y) = inverse.apply(point.x as f64, point.y as f64);
+                        let normalized = Normalized::new(
+                            (x + 0.5) / self.capture.width as f64,
+                            (y + 0.5) / self.capture.height as f64,
+                        );
+                        normalized.check()?;
+                        (normalized.x, normalized.y)
+                    }
+                    None => self.rotation.input_to_capture(x, y),
+                }
+            }
+        };
+        Ok(Normalized::new(x, y))
+    }
+
+    pub fn to_capture(&self, point: impl Into<Point>) -> Result<CapturePoint, CoordError> {
+        let point = point.into();
+        if let Point::Capture(point) = point {
+            self.capture.normalize(point.x, point.y, "Capture")?;
+            return Ok(point);
+        }
+
+        let normalized = self.to_normalized(point)?;
+        let (x, y) = self.capture.pixel(normalized.x, normalized.y)?;
+        Ok(CapturePoint::new(x, y))
+    }
+
+    pub fn to_input(&self, point: impl Into<Point>) -> Result<InputPoint, CoordError> {
+        let point = point.into();
+        if let Point::Input(point) = point {
+            self.input.normalize(point.x, point.y, "Input")?;
+            return Ok(point);
+        }
+
+        let normalized = self.to_normalized(point)?;
+        if let Some(calibration) = self.calibration {
+            let (x, y) = calibration.apply(
+                normalized.x * self.capture.width as f64 - 0.5,
+                normalized.y * self.capture.height as f64 - 0.5,
+            );
+            let (x, y) = (x.round() as i32, y.round() as i32);
+            self.input.normalize(x, y, "Input")?;
+            return Ok(InputPoint::new(x, y));
+        }
+
+        let (x, y) = self.rotation.capture_to_input(normalized.x, normalized.y);
+        let (x, y) = self.input.pixel(x, y)?;
+        Ok(InputPoint::new(x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_round_trip() -> Result<(), CoordError> {
+        let space = CoordSpace::identity(Size::new(1280, 720));
+
+        for (x, y) in [(0, 0), (1279, 719), (640, 360)] {
+            let normalized = space.to_normalized(CapturePoint::new(x, y))?;
+            assert_eq!(space.to_capture(normalized)?, CapturePoint::new(x, y));
+            assert_eq!(space.to_input(normalized)?, InputPoint::new(x, y));
+        }
+        assert_eq!(
+            space.to_input(Normalized::new(1.0, 1.0))?,
+            InputPoint::new(1279, 719)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scaling() -> Result<(), CoordError> {
+        // Frames captured at half the input resolution
+        let space = CoordSpace::new(Size::new(640, 360), Size::new(1280, 720), Rotation::R0);
+
+        assert_eq!(
+            space.to_input(CapturePoint::new(100, 50))?,
+            InputPoint::new(201, 101)
+        );
+        assert_eq!(
+            space.to_capture(InputPoint::new(201, 101))?,
+            CapturePoint::new(100, 50)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotation() -> Result<(), CoordError> {
+        // Portrait panel, landscape frame
+        let input = Size::new(720, 1280);
+        let capture = input.transposed();
+
+        let r90 = CoordSpace::new(capture, input, Rotation::R90);
+        // Top-left of the frame is the bottom-left of the panel
+        assert_eq!(
+            r90.to_input(CapturePoint::new(0, 0))?,
+            InputPoint::new(0, 1279)
+        );
+        assert_eq!(
+            r90.to_input(CapturePoint::new(1279, 0))?,
+            InputPoint::new(0, 0)
+        );
+
+        let r270 = CoordSpace::new(capture, input, Rotation::R270);
+        assert_eq!(
+            r270.to_input(CapturePoint::new(0, 0))?,
+            InputPoint::new(719, 0)
+        );
+
+        let r180 = CoordSpace::new(input, input, Rotation::R180);
+        assert_eq!(
+            r180.to_input(CapturePoint::new(0, 0))?,
+            InputPoint::new(719, 1279)
+        );
+
+        for rotation in (0..4).map(Rotation::from_quarter_turns) {
+            let capture = if rotation.is_transposed() {
+                input.transposed()
+            } else {
+                input
+            };
+            let space = CoordSpace::new(capture, input, rotation);
+            let point = CapturePoint::new(100, 200);
+            assert_eq!(space.to_capture(space.to_input(point)?)?, point);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calibration() -> Result<(), CoordError> {
+        // Measured: input is twice the capture resolution and shifted by 10 pixels
+        let calibration = Affine {
+            a: 2.0,
+            b: 0.0,
+            c: 10.0,
+            d: 0.0,
+            e: 2.0,
+            f: 10.0,
+        };
+        let space = CoordSpace::new(Size::new(100, 100), Size::new(220, 220), Rotation::R0)
+            .with_calibration(calibration);
+
+        assert_eq!(
+            space.to_input(CapturePoint::new(50, 20))?,
+            InputPoint::new(110, 50)
+        );
+        assert_eq!(
+            space.to_capture(InputPoint::new(110, 50))?,
+            CapturePoint::new(50, 20)
+        );
+        assert!(matches!(
+            space.to_capture(InputPoint::new(0, 0)),
+            Err(CoordError::OutOfBounds { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let space = CoordSpace::identity(Size::new(100, 100));
+
+        assert!(matches!(
+            space.to_input(Normalized::new(1.2, 0.5)),
+            Err(CoordError::OutOfBounds {
+                space: "Normalized",
+                ..
+            })
+        ));
+        assert!(matches!(
+            space.to_input(CapturePoint::new(100, 0)),
+            Err(CoordError::OutOfBounds {
+                space: "Capture",
+                ..
+            })
+        ));
+        assert!(matches!(
+            space.to_input(InputPoint::new(-1, 0)),
+            Err(CoordError::OutOfBounds { space: "Input", .. })
+        ));
+        assert!(matches!(
+            CoordSpace::default().to_input(Normalized::new(0.5, 0.5)),
+            Err(CoordError::EmptySpace)
+        ));
+    }
+}